@@ -0,0 +1,35 @@
+/* C declarations of the plugin ABI, see src/plugin.rs. */
+#ifndef COMPRESSION_PLUGIN_H
+#define COMPRESSION_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define COMPRESSION_PLUGIN_ABI_VERSION 1
+
+typedef struct {
+    /* NUL-terminated UTF-8, valid while the library is loaded */
+    const char *name;
+    /* may be NULL */
+    const char *settings;
+} PluginDescription;
+
+/* all functions have to be thread-safe */
+typedef struct {
+    /* passed unchanged to every function */
+    uintptr_t context;
+    PluginDescription (*describe)(uintptr_t context);
+    size_t (*compress_bound)(uintptr_t context, size_t src_len);
+    /* returns 0 on success, the compressed size is stored in *dst_len */
+    int (*compress)(uintptr_t context, const uint8_t *src, size_t src_len, uint8_t *dst,
+                    size_t dst_capacity, size_t *dst_len);
+    /* returns 0 only if the decompressed data is exactly dst_len bytes long */
+    int (*decompress)(uintptr_t context, const uint8_t *src, size_t src_len, uint8_t *dst,
+                      size_t dst_len);
+} PluginCodec;
+
+/* exported by the plugin */
+uint32_t compression_plugin_abi_version(void);
+const PluginCodec *compression_plugin_codecs(size_t *count);
+
+#endif
@@ -0,0 +1,591 @@
+//! Compressing and decompressing files with any scheme, for the `ccmp` tool in `tools/ccmp`.
+//!
+//! `ccmp` runs a scheme crate with a request in the environment variables below.
+//! The `benchmark` functions then handle the request with the matching scheme of their list
+//! instead of benchmarking, so every scheme crate supports it without changes.
+//! A handled request prints one line to stdout, a request that no scheme matches prints nothing.
+//! `list`, `detect` and `probe` print a csv row for every scheme instead.
+
+use crate::{Compressor, Decompressor, DescribeScheme};
+use anyhow::Context as _;
+use std::io::Write as _;
+
+/// `list`, `detect`, `compress`, `decompress`, `roundtrip`, `probe` or `benchmark`.
+pub const COMMAND_VAR: &str = "CCMP_COMMAND";
+pub const SCHEME_VAR: &str = "CCMP_SCHEME";
+/// Unset for schemes without settings.
+pub const SETTINGS_VAR: &str = "CCMP_SETTINGS";
+pub const INPUT_VAR: &str = "CCMP_INPUT";
+/// Unset for `roundtrip`, `probe` and `benchmark`.
+pub const OUTPUT_VAR: &str = "CCMP_OUTPUT";
+
+pub const MAGIC: &[u8; 4] = b"CCMP";
+/// Bumped on every incompatible change of the header.
+pub const VERSION: u8 = 1;
+
+/// Precedes the compressed data in files written by `ccmp compress`: the magic bytes, the version,
+/// the scheme name and settings (each a flag whether present, then a little-endian `u16` length
+/// and UTF-8) and the uncompressed length as a little-endian `u64`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub scheme: String,
+    pub settings: Option<String>,
+    pub uncompressed_len: u64,
+}
+
+fn write_string<W: std::io::Write>(writer: &mut W, string: Option<&str>) -> anyhow::Result<()> {
+    match string {
+        Some(string) => {
+            let len = u16::try_from(string.len()).context("string too long for the header")?;
+            writer.write_all(&[1])?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(string.as_bytes())?;
+        }
+        None => writer.write_all(&[0])?,
+    }
+    Ok(())
+}
+
+fn read_string<R: std::io::Read>(reader: &mut R) -> anyhow::Result<Option<String>> {
+    let mut present = [0u8];
+    reader.read_exact(&mut present)?;
+    match present[0] {
+        0 => Ok(None),
+        1 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            let mut string = vec![0u8; usize::from(u16::from_le_bytes(len))];
+            reader.read_exact(&mut string)?;
+            Ok(Some(
+                String::from_utf8(string).context("string isn't UTF-8")?,
+            ))
+        }
+        _ => anyhow::bail!("invalid string flag"),
+    }
+}
+
+impl Header {
+    pub fn write_to<W: std::io::Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        write_string(writer, Some(&self.scheme))?;
+        write_string(writer, self.settings.as_deref())?;
+        writer.write_all(&self.uncompressed_len.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Read the header and leave `reader` at the start of the compressed data.
+    pub fn read_from<R: std::io::Read>(reader: &mut R) -> anyhow::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader
+            .read_exact(&mut magic)
+            .context("input is too short for a ccmp header")?;
+        anyhow::ensure!(&magic == MAGIC, "input isn't a ccmp file");
+        let mut version = [0u8];
+        reader.read_exact(&mut version)?;
+        anyhow::ensure!(
+            version[0] == VERSION,
+            "ccmp header version {} isn't supported",
+            version[0]
+        );
+        let scheme = read_string(reader)
+            .context("invalid scheme in ccmp header")?
+            .context("ccmp header has no scheme")?;
+        let settings = read_string(reader).context("invalid settings in ccmp header")?;
+        let mut uncompressed_len = [0u8; 8];
+        reader.read_exact(&mut uncompressed_len)?;
+        Ok(Self {
+            scheme,
+            settings,
+            uncompressed_len: u64::from_le_bytes(uncompressed_len),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    List,
+    /// Check format detection against the output of every scheme.
+    Detect,
+    Compress,
+    Decompress,
+    Roundtrip,
+    /// Compress and decompress a part of the input once with every scheme, in parallel, for a
+    /// rough estimate of the ratios and speeds on the data of the user, see [`crate::probe`].
+    Probe,
+    /// Benchmark a scheme on the input like on the corpora.
+    Benchmark,
+}
+
+impl std::str::FromStr for Command {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "list" => Ok(Command::List),
+            "detect" => Ok(Command::Detect),
+            "compress" => Ok(Command::Compress),
+            "decompress" => Ok(Command::Decompress),
+            "roundtrip" => Ok(Command::Roundtrip),
+            "probe" => Ok(Command::Probe),
+            "benchmark" => Ok(Command::Benchmark),
+            _ => anyhow::bail!("unknown ccmp command {s}"),
+        }
+    }
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Command::List => "list",
+            Command::Detect => "detect",
+            Command::Compress => "compress",
+            Command::Decompress => "decompress",
+            Command::Roundtrip => "roundtrip",
+            Command::Probe => "probe",
+            Command::Benchmark => "benchmark",
+        })
+    }
+}
+
+/// Which speeds of a scheme are measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Timing {
+    Both,
+    /// `compress` only prepares the input for `decompress_to`, see
+    /// [`crate::benchmark_decompression_only`].
+    DecompressionOnly,
+}
+
+/// The name of the corpus in the rows of `probe` and `benchmark`.
+const PROBE_CORPUS: &str = "probe";
+
+pub(crate) struct Request {
+    command: Command,
+    scheme: Option<String>,
+    settings: Option<String>,
+    input: Option<std::path::PathBuf>,
+    output: Option<std::path::PathBuf>,
+}
+
+#[derive(serde::Serialize)]
+struct DetectRow {
+    scheme: String,
+    settings: Option<String>,
+    format: Option<String>,
+    // all formats detected in the output for the samples
+    detected: String,
+}
+
+/// Small inputs of different kinds.
+fn detection_samples() -> [(&'static str, std::vec::Vec<u8>); 3] {
+    [
+        ("zeros", crate::synthetic::zeros(1 << 16)),
+        (
+            "uniform random",
+            crate::synthetic::uniform_random(1 << 16, 1),
+        ),
+        ("json logs", crate::synthetic::json_logs(1 << 16, 1)),
+    ]
+}
+
+impl Request {
+    /// The request in the environment, if any.
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(command) = std::env::var_os(COMMAND_VAR) else {
+            return Ok(None);
+        };
+        let var = |name| {
+            std::env::var_os(name)
+                .map(|value| {
+                    value
+                        .into_string()
+                        .map_err(|_| anyhow::Error::msg(format!("{name} isn't UTF-8")))
+                })
+                .transpose()
+        };
+        Ok(Some(Self {
+            command: command.to_str().context("invalid ccmp command")?.parse()?,
+            scheme: var(SCHEME_VAR)?,
+            settings: var(SETTINGS_VAR)?,
+            input: std::env::var_os(INPUT_VAR).map(Into::into),
+            output: std::env::var_os(OUTPUT_VAR).map(Into::into),
+        }))
+    }
+
+    fn print_scheme<C: DescribeScheme + ?Sized>(&self, scheme: &C) -> anyhow::Result<()> {
+        crate::print_row(&mut std::io::stdout(), (scheme.name(), scheme.settings()))
+            .context("couldn't print scheme to stdout")
+    }
+
+    fn matches<C: DescribeScheme + ?Sized>(&self, scheme: &C) -> bool {
+        self.scheme.as_ref() == Some(&scheme.name()) && self.settings == scheme.settings()
+    }
+
+    /// Compress each detection sample with `scheme` and check that the format it declares is
+    /// detected, returns the samples and the compressed data.
+    /// The output of schemes without a format can look like any format by chance, so what is
+    /// detected there is only reported.
+    fn check_detection<C: Compressor + DescribeScheme + ?Sized>(
+        &self,
+        scheme: &C,
+    ) -> anyhow::Result<std::vec::Vec<(std::vec::Vec<u8>, std::vec::Vec<u8>)>> {
+        let mut samples = vec![];
+        let mut detected_formats = vec![];
+        for (name, data) in detection_samples() {
+            let compressed = scheme
+                .compress(&data)
+                .with_context(|| format!("couldn't compress {name} sample"))?;
+            let detected = crate::detect::detect(&compressed);
+            if let Some(format) = scheme.format() {
+                anyhow::ensure!(
+                    detected == Some(format),
+                    "detected format {detected:?} in the output of {} for the {name} sample, but \
+                     it declares {format:?}",
+                    describe(scheme),
+                );
+            }
+            if let Some(detected) = detected.filter(|f| !detected_formats.contains(f)) {
+                detected_formats.push(detected);
+            }
+            samples.push((data, compressed));
+        }
+        let row = DetectRow {
+            scheme: scheme.name(),
+            settings: scheme.settings(),
+            format: scheme.format().map(|format| format.to_string()),
+            detected: detected_formats
+                .iter()
+                .map(|format| format.to_string())
+                .collect::<std::vec::Vec<_>>()
+                .join(" / "),
+        };
+        crate::print_row(&mut std::io::stdout(), row).context("couldn't print scheme to stdout")?;
+        Ok(samples)
+    }
+
+    /// Handle the request with `schemes`: print all of them for `list`, check the format
+    /// detection for all of them for `detect`, probe all of them for `probe`, and otherwise run
+    /// the one that matches, if any.
+    pub(crate) fn handle<
+        C: Compressor + Decompressor + DescribeScheme + ?Sized,
+        S: std::borrow::Borrow<C> + Sync,
+        I: IntoIterator<Item = S>,
+    >(
+        &self,
+        schemes: I,
+        timing: Timing,
+    ) -> anyhow::Result<()> {
+        if self.command == Command::Probe {
+            let probe = match timing {
+                Timing::Both => probe_scheme,
+                Timing::DecompressionOnly => probe_decompression_scheme,
+            };
+            return self.probe(schemes, probe);
+        }
+        for scheme in schemes {
+            let scheme = scheme.borrow();
+            match self.command {
+                Command::List => self.print_scheme(scheme)?,
+                Command::Detect => {
+                    let samples = self.check_detection(scheme)?;
+                    let Some(format) = scheme.format() else {
+                        continue;
+                    };
+                    // decompress through the detector, like data of unknown provenance
+                    let detector = crate::detect::Detector::new().with(format, scheme);
+                    for (data, compressed) in samples {
+                        let mut decompressed = vec![0u8; data.len()];
+                        detector
+                            .decompress_to(&compressed, &mut decompressed)
+                            .with_context(|| format!("{} failed", describe(scheme)))?;
+                        anyhow::ensure!(
+                            decompressed == data,
+                            "CRITICAL BUG: decompress(compress(x)) != x"
+                        );
+                    }
+                }
+                _ if self.matches(scheme) => return self.run(scheme, timing),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::handle`], for schemes that can't decompress.
+    pub(crate) fn handle_compression_only<
+        C: Compressor + DescribeScheme,
+        S: std::borrow::Borrow<C> + Sync,
+        I: IntoIterator<Item = S>,
+    >(
+        &self,
+        schemes: I,
+    ) -> anyhow::Result<()> {
+        if self.command == Command::Probe {
+            return self.probe(schemes, probe_compression_scheme);
+        }
+        for scheme in schemes {
+            let scheme = scheme.borrow();
+            match self.command {
+                Command::List => self.print_scheme(scheme)?,
+                Command::Detect => {
+                    self.check_detection(scheme)?;
+                }
+                _ if self.matches(scheme) => return self.run_compression_only(scheme),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `probe` with every scheme on the [sweep sample](crate::probe::sweep_sample) of the
+    /// input, in parallel like the ratios of the corpora, and print the results.
+    fn probe<
+        C: DescribeScheme + ?Sized,
+        S: std::borrow::Borrow<C> + Sync,
+        I: IntoIterator<Item = S>,
+    >(
+        &self,
+        schemes: I,
+        probe: fn(&C, &crate::Corpus) -> anyhow::Result<crate::Result>,
+    ) -> anyhow::Result<()> {
+        let corpus = crate::Corpus {
+            name: PROBE_CORPUS,
+            data: crate::probe::sweep_sample(&self.input()?),
+        };
+        let schemes: std::vec::Vec<S> = schemes.into_iter().collect();
+        for result in crate::ratios(&schemes, std::slice::from_ref(&corpus), probe)? {
+            crate::print_result(&mut std::io::stdout(), result)
+                .context("couldn't print result to stdout")?;
+        }
+        Ok(())
+    }
+
+    fn input(&self) -> anyhow::Result<std::vec::Vec<u8>> {
+        let path = self.input.as_ref().context("no input file")?;
+        std::fs::read(path).with_context(|| format!("couldn't read {}", path.display()))
+    }
+
+    fn corpus(&self) -> anyhow::Result<crate::Corpus> {
+        Ok(crate::Corpus {
+            name: PROBE_CORPUS,
+            data: self.input()?,
+        })
+    }
+
+    fn write_output(&self, header: Option<&Header>, data: &[u8]) -> anyhow::Result<()> {
+        let path = self.output.as_ref().context("no output file")?;
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("couldn't create {}", path.display()))?,
+        );
+        if let Some(header) = header {
+            header.write_to(&mut file)?;
+        }
+        file.write_all(data)?;
+        file.flush()
+            .with_context(|| format!("couldn't write {}", path.display()))
+    }
+
+    fn compress<C: Compressor + DescribeScheme + ?Sized>(&self, scheme: &C) -> anyhow::Result<()> {
+        let data = self.input()?;
+        let compressed = scheme.compress(&data).context("compression failed")?;
+        let header = Header {
+            scheme: scheme.name(),
+            settings: scheme.settings(),
+            uncompressed_len: data.len() as u64,
+        };
+        self.write_output(Some(&header), &compressed)?;
+        println!(
+            "{}: {} -> {} bytes",
+            describe(scheme),
+            data.len(),
+            compressed.len()
+        );
+        Ok(())
+    }
+
+    /// Handle the request with `scheme`, which can't decompress.
+    pub(crate) fn run_compression_only<C: Compressor + DescribeScheme + ?Sized>(
+        &self,
+        scheme: &C,
+    ) -> anyhow::Result<()> {
+        match self.command {
+            Command::List | Command::Detect | Command::Probe => Ok(()),
+            Command::Compress => self.compress(scheme),
+            Command::Benchmark => {
+                let result =
+                    crate::benchmark_compression_scheme(scheme, &self.corpus()?, crate::SAMPLES)?;
+                crate::print_result(&mut std::io::stdout(), result)
+                    .context("couldn't print result to stdout")
+            }
+            Command::Decompress | Command::Roundtrip => anyhow::bail!(
+                "{} is only benchmarked for compression and can't decompress",
+                describe(scheme)
+            ),
+        }
+    }
+
+    /// Handle the request with `scheme`.
+    pub(crate) fn run<C: Compressor + Decompressor + DescribeScheme + ?Sized>(
+        &self,
+        scheme: &C,
+        timing: Timing,
+    ) -> anyhow::Result<()> {
+        match self.command {
+            Command::List | Command::Detect | Command::Probe => Ok(()),
+            Command::Compress => self.compress(scheme),
+            Command::Benchmark => {
+                let corpus = self.corpus()?;
+                let result = match timing {
+                    Timing::Both => crate::benchmark_scheme(scheme, &corpus, crate::SAMPLES),
+                    Timing::DecompressionOnly => {
+                        crate::benchmark_decompression_scheme(scheme, &corpus, crate::SAMPLES)
+                    }
+                }?;
+                crate::print_result(&mut std::io::stdout(), result)
+                    .context("couldn't print result to stdout")
+            }
+            Command::Decompress => {
+                let data = self.input()?;
+                let decompressed = if data.starts_with(MAGIC) {
+                    let mut src = &data[..];
+                    let header = Header::read_from(&mut src)?;
+                    anyhow::ensure!(
+                        header.scheme == scheme.name() && header.settings == scheme.settings(),
+                        "input was compressed with another scheme"
+                    );
+                    let len = usize::try_from(header.uncompressed_len)
+                        .context("uncompressed length doesn't fit in memory")?;
+                    // the header is untrusted, so a forged length must fail instead of aborting
+                    let mut decompressed = vec![];
+                    decompressed
+                        .try_reserve_exact(len)
+                        .context("uncompressed length in the header is too large")?;
+                    decompressed.resize(len, 0);
+                    scheme
+                        .decompress_to(src, &mut decompressed)
+                        .context("decompression failed")?;
+                    decompressed
+                } else {
+                    // data of unknown provenance, for `ccmp decompress --detect`
+                    let format = crate::detect::detect(&data).context("unknown format")?;
+                    anyhow::ensure!(
+                        scheme.format() == Some(format),
+                        "input is {format}, which {} doesn't decompress",
+                        describe(scheme)
+                    );
+                    scheme.decompress(&data).context("decompression failed")?
+                };
+                self.write_output(None, &decompressed)?;
+                println!(
+                    "{}: {} -> {} bytes",
+                    describe(scheme),
+                    data.len(),
+                    decompressed.len()
+                );
+                Ok(())
+            }
+            Command::Roundtrip => {
+                let data = self.input()?;
+                let (compressed, compression_time) =
+                    crate::time(|| scheme.compress(&data)).context("compression failed")?;
+                let mut decompressed = vec![0u8; data.len()];
+                let ((), decompression_time) =
+                    crate::time(|| scheme.decompress_to(&compressed, &mut decompressed))
+                        .context("decompression failed")?;
+                anyhow::ensure!(
+                    decompressed == data,
+                    "CRITICAL BUG: decompress(compress(x)) != x"
+                );
+                let size_mb = data.len() as f64 / 1_000_000.0f64;
+                println!(
+                    "{}: {} -> {} bytes, compression ratio {:.3}, compression {:.1} MB/s, decompression {:.1} MB/s",
+                    describe(scheme),
+                    data.len(),
+                    compressed.len(),
+                    data.len() as f64 / compressed.len() as f64,
+                    size_mb / compression_time.as_secs_f64(),
+                    size_mb / decompression_time.as_secs_f64(),
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+fn size_mb(corpus: &crate::Corpus) -> f64 {
+    corpus.data.len() as f64 / 1_000_000.0f64
+}
+
+/// Compress `corpus` once with `scheme`, for the compressed data and a result with the speed of
+/// the single run, which is only a rough estimate, all the more next to the other schemes of the
+/// sweep on the other cores.
+fn probe_compression<C: Compressor + DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &crate::Corpus,
+) -> anyhow::Result<(std::vec::Vec<u8>, crate::Result)> {
+    let (compressed, t) = crate::time(|| scheme.compress(&corpus.data))
+        .with_context(|| format!("{} failed to compress", describe(scheme)))?;
+    let result = crate::Result {
+        scheme: scheme.name(),
+        settings: scheme.settings(),
+        corpus: corpus.name,
+        compression_speed: Some(size_mb(corpus) / t.as_secs_f64()),
+        compression_speed_std: None,
+        decompression_speed: None,
+        decompression_speed_std: None,
+        compression_ratio: corpus.data.len() as f64 / compressed.len() as f64,
+    };
+    Ok((compressed, result))
+}
+
+fn probe_compression_scheme<C: Compressor + DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &crate::Corpus,
+) -> anyhow::Result<crate::Result> {
+    Ok(probe_compression(scheme, corpus)?.1)
+}
+
+/// Like [`probe_compression`], and decompress once more for the decompression speed.
+fn probe_scheme<C: Compressor + Decompressor + DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &crate::Corpus,
+) -> anyhow::Result<crate::Result> {
+    let (compressed, mut result) = probe_compression(scheme, corpus)?;
+    let mut decompressed = vec![0u8; corpus.data.len()];
+    let ((), t) = crate::time(|| scheme.decompress_to(&compressed, &mut decompressed))
+        .with_context(|| format!("{} failed to decompress", describe(scheme)))?;
+    anyhow::ensure!(
+        decompressed == corpus.data,
+        "CRITICAL BUG: decompress(compress(x)) != x"
+    );
+    result.decompression_speed = Some(size_mb(corpus) / t.as_secs_f64());
+    Ok(result)
+}
+
+/// Like [`probe_scheme`], for schemes that only time decompression.
+fn probe_decompression_scheme<C: Compressor + Decompressor + DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &crate::Corpus,
+) -> anyhow::Result<crate::Result> {
+    let mut result = probe_scheme(scheme, corpus)?;
+    result.compression_speed = None;
+    Ok(result)
+}
+
+fn describe<C: DescribeScheme + ?Sized>(scheme: &C) -> String {
+    match scheme.settings() {
+        Some(settings) => format!("{} ({})", scheme.name(), settings),
+        None => scheme.name(),
+    }
+}
+
+/// Read the header of the ccmp file at `path`.
+pub fn read_header<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Header> {
+    let path = path.as_ref();
+    let mut file = std::io::BufReader::new(
+        std::fs::File::open(path).with_context(|| format!("couldn't open {}", path.display()))?,
+    );
+    Header::read_from(&mut file)
+        .with_context(|| format!("couldn't read ccmp header of {}", path.display()))
+}
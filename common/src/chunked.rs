@@ -0,0 +1,188 @@
+use crate::{Compressor, Decompressor, DescribeScheme};
+use anyhow::Context as _;
+
+/// Splits the input into independent chunks that are compressed and decompressed in parallel.
+///
+/// The compressed chunks are framed by a small index:
+/// the number of chunks followed by the uncompressed and compressed length of each chunk
+/// (all little-endian `u64`), then the compressed chunks in order.
+pub struct Chunked<C> {
+    pub inner: C,
+    pub chunk_size: std::num::NonZeroUsize,
+    pub threads: std::num::NonZeroUsize,
+}
+
+const INDEX_ENTRY_SIZE: usize = 8;
+
+fn read_u64(src: &[u8], pos: &mut usize) -> anyhow::Result<usize> {
+    let bytes = src
+        .get(*pos..*pos + INDEX_ENTRY_SIZE)
+        .context("chunk index truncated")?;
+    *pos += INDEX_ENTRY_SIZE;
+    let value = u64::from_le_bytes(bytes.try_into().unwrap());
+    value.try_into().context("chunk index entry too large")
+}
+
+impl<C: DescribeScheme> DescribeScheme for Chunked<C> {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+    fn settings(&self) -> Option<String> {
+        let chunked = format!("chunk size {} / threads {}", self.chunk_size, self.threads);
+        match self.inner.settings() {
+            Some(settings) => Some(format!("{settings} / {chunked}")),
+            None => Some(chunked),
+        }
+    }
+    fn threads(&self) -> std::num::NonZeroUsize {
+        self.threads.saturating_mul(self.inner.threads())
+    }
+}
+
+impl<C: Compressor + Sync> Compressor for Chunked<C> {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let chunks: std::vec::Vec<&[u8]> = data.chunks(self.chunk_size.get()).collect();
+        let compressed = crate::parallel::map(self.threads, chunks, |chunk| {
+            Ok((chunk.len(), self.inner.compress(chunk)?))
+        })
+        .context("chunked compression failed")?;
+
+        let index_size = INDEX_ENTRY_SIZE * (1 + 2 * compressed.len());
+        let data_size: usize = compressed.iter().map(|(_, chunk)| chunk.len()).sum();
+        let mut vec = std::vec::Vec::with_capacity(index_size + data_size);
+        vec.extend_from_slice(&(compressed.len() as u64).to_le_bytes());
+        for (uncompressed_len, chunk) in compressed.iter() {
+            vec.extend_from_slice(&(*uncompressed_len as u64).to_le_bytes());
+            vec.extend_from_slice(&(chunk.len() as u64).to_le_bytes());
+        }
+        for (_, chunk) in compressed.iter() {
+            vec.extend_from_slice(chunk);
+        }
+        Ok(vec)
+    }
+}
+
+impl<C: Decompressor + Sync> Decompressor for Chunked<C> {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let mut pos = 0;
+        let count = read_u64(src, &mut pos)?;
+        let mut lengths = std::vec::Vec::with_capacity(count.min(src.len() / INDEX_ENTRY_SIZE));
+        for _ in 0..count {
+            let uncompressed_len = read_u64(src, &mut pos)?;
+            let compressed_len = read_u64(src, &mut pos)?;
+            lengths.push((uncompressed_len, compressed_len));
+        }
+
+        let mut jobs = std::vec::Vec::with_capacity(lengths.len());
+        let mut src_rest = &src[pos..];
+        let mut dst_rest = dst;
+        for (uncompressed_len, compressed_len) in lengths {
+            anyhow::ensure!(
+                compressed_len <= src_rest.len(),
+                "chunked decompression error: compressed data too short"
+            );
+            anyhow::ensure!(
+                uncompressed_len <= dst_rest.len(),
+                "chunked decompression error: dst too short"
+            );
+            let (src_chunk, src_tail) = src_rest.split_at(compressed_len);
+            let (dst_chunk, dst_tail) =
+                std::mem::take(&mut dst_rest).split_at_mut(uncompressed_len);
+            jobs.push((src_chunk, dst_chunk));
+            src_rest = src_tail;
+            dst_rest = dst_tail;
+        }
+        anyhow::ensure!(
+            src_rest.is_empty(),
+            "chunked decompression error: trailing compressed data"
+        );
+        anyhow::ensure!(
+            dst_rest.is_empty(),
+            "chunked decompression error: dst too long"
+        );
+
+        crate::parallel::map(self.threads, jobs, |(src, dst)| {
+            self.inner.decompress_to(src, dst)
+        })
+        .context("chunked decompression failed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stores the data reversed, so that chunks decompressed in the wrong place show.
+    struct Reverse;
+
+    impl Compressor for Reverse {
+        fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+    }
+
+    impl Decompressor for Reverse {
+        fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+            anyhow::ensure!(src.len() == dst.len(), "dst buffer length mismatch");
+            for (d, s) in dst.iter_mut().zip(src.iter().rev()) {
+                *d = *s;
+            }
+            Ok(())
+        }
+    }
+
+    fn chunked(chunk_size: usize, threads: usize) -> Chunked<Reverse> {
+        Chunked {
+            inner: Reverse,
+            chunk_size: std::num::NonZeroUsize::new(chunk_size).unwrap(),
+            threads: std::num::NonZeroUsize::new(threads).unwrap(),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        for len in [0, 1, 4096, 10_000] {
+            let data = crate::synthetic::uniform_random(len, 1);
+            for chunk_size in [1, 1000, 4096, 1 << 20] {
+                for threads in [1, 2, 4] {
+                    let scheme = chunked(chunk_size, threads);
+                    let compressed = scheme.compress(&data).unwrap();
+                    let mut decompressed = vec![0u8; len];
+                    scheme
+                        .decompress_to(&compressed, &mut decompressed)
+                        .unwrap();
+                    assert_eq!(decompressed, data, "{len} bytes in chunks of {chunk_size}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let scheme = chunked(1000, 2);
+        let data = crate::synthetic::uniform_random(2500, 1);
+        let compressed = scheme.compress(&data).unwrap();
+        let mut dst = vec![0u8; data.len()];
+
+        for len in [0, 4, 8, 20, compressed.len() - 1] {
+            assert!(scheme.decompress_to(&compressed[..len], &mut dst).is_err());
+        }
+        let mut trailing = compressed.clone();
+        trailing.push(0);
+        assert!(scheme.decompress_to(&trailing, &mut dst).is_err());
+
+        // more chunks than the index and the data hold, up to a count that would overflow the
+        // index size
+        for count in [4, 1 << 40, u64::MAX] {
+            let mut inflated = compressed.clone();
+            inflated[..8].copy_from_slice(&count.to_le_bytes());
+            assert!(scheme.decompress_to(&inflated, &mut dst).is_err());
+        }
+
+        for len in [0, data.len() - 1, data.len() + 1] {
+            let mut dst = vec![0u8; len];
+            assert!(scheme.decompress_to(&compressed, &mut dst).is_err());
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{Compressor, Decompressor, DescribeScheme};
+use anyhow::Context as _;
+use std::io::{Read as _, Write as _};
+
+/// Runs an external program for every compression and decompression, passing the input through
+/// stdin and reading the output from stdout, e.g. `["zstd", "-3", "-c"]` and `["zstd", "-d", "-c"]`.
+///
+/// The measured time includes spawning the process, which the settings point out.
+pub struct ExternalCommand {
+    pub name: String,
+    pub compress: std::vec::Vec<String>,
+    pub decompress: std::vec::Vec<String>,
+    /// The format of the program's output, see [`DescribeScheme::format`].
+    pub format: Option<crate::detect::Format>,
+    /// The threads the program runs with, e.g. for `xz -T4`, see [`DescribeScheme::threads`].
+    pub threads: std::num::NonZeroUsize,
+}
+
+impl ExternalCommand {
+    pub fn new(name: &str, compress: &[&str], decompress: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            compress: compress.iter().map(|arg| arg.to_string()).collect(),
+            decompress: decompress.iter().map(|arg| arg.to_string()).collect(),
+            format: None,
+            threads: std::num::NonZeroUsize::MIN,
+        }
+    }
+
+    pub fn with_format(mut self, format: crate::detect::Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_threads(mut self, threads: std::num::NonZeroUsize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Whether both programs can be spawned, i.e. they are installed.
+    pub fn is_available(&self) -> bool {
+        [&self.compress, &self.decompress].into_iter().all(|argv| {
+            argv.first().is_some_and(|program| {
+                std::process::Command::new(program)
+                    .arg("--version")
+                    .stdin(std::process::Stdio::null())
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .status()
+                    .is_ok()
+            })
+        })
+    }
+}
+
+/// Spawn `argv`, write `input` to its stdin from another thread (so that a full stdout pipe
+/// can't deadlock), and hand its stdout to `read`.
+fn run<T>(
+    argv: &[String],
+    input: &[u8],
+    read: impl FnOnce(&mut std::process::ChildStdout) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let (program, args) = argv.split_first().context("empty command")?;
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("couldn't run {program}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let (output, written) = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(input));
+        let output = read(&mut stdout);
+        if output.is_err() {
+            // unblock the writer, the output is wrong anyway
+            let _ = child.kill();
+        }
+        drop(stdout);
+        (output, writer.join())
+    });
+    let status = child
+        .wait()
+        .with_context(|| format!("couldn't wait for {program}"))?;
+    let output = output?;
+    written
+        .map_err(|_| anyhow::Error::msg("stdin writer thread panicked"))?
+        .with_context(|| format!("couldn't write to stdin of {program}"))?;
+    anyhow::ensure!(status.success(), "{program} failed: {status}");
+    Ok(output)
+}
+
+impl DescribeScheme for ExternalCommand {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(format!(
+            "{} / includes process spawn",
+            self.compress.join(" ")
+        ))
+    }
+    fn format(&self) -> Option<crate::detect::Format> {
+        self.format
+    }
+    fn threads(&self) -> std::num::NonZeroUsize {
+        self.threads
+    }
+}
+
+impl Compressor for ExternalCommand {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        run(&self.compress, data, |stdout| {
+            let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+            stdout.read_to_end(&mut vec)?;
+            Ok(vec)
+        })
+        .context("external compression failed")
+    }
+}
+
+impl Decompressor for ExternalCommand {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        run(&self.decompress, src, |stdout| {
+            stdout.read_exact(dst)?;
+            let mut tmp = [0u8];
+            match stdout.read_exact(&mut tmp) {
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+                _ => Err(anyhow::Error::msg("dst too short")),
+            }
+        })
+        .context("external decompression failed")
+    }
+}
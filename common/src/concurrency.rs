@@ -0,0 +1,330 @@
+use crate::{Compressor, Corpus, Decompressor, DescribeScheme};
+use anyhow::Context as _;
+use average::Estimate as _;
+
+/// Sizes of the independent blocks that each thread compresses and decompresses.
+pub const BLOCK_SIZES: [usize; 2] = [1 << 16, 1 << 20];
+
+#[derive(serde::Serialize)]
+pub struct ConcurrencyResult {
+    pub scheme: String,
+    pub settings: Option<String>,
+    pub corpus: &'static str,
+    pub threads: usize,
+    pub block_size: usize,
+    pub compression_throughput: f64,
+    pub compression_throughput_std: f64,
+    pub decompression_throughput: f64,
+    pub decompression_throughput_std: f64,
+    pub compression_latency: f64,
+    pub compression_latency_std: f64,
+    pub decompression_latency: f64,
+    pub decompression_latency_std: f64,
+    pub compression_thread_time: f64,
+    pub compression_thread_time_std: f64,
+    pub compression_thread_time_max: f64,
+    pub decompression_thread_time: f64,
+    pub decompression_thread_time_std: f64,
+    pub decompression_thread_time_max: f64,
+    pub compression_ratio: f64,
+}
+
+struct Worker {
+    compression: std::ops::Range<std::time::Instant>,
+    decompression: std::ops::Range<std::time::Instant>,
+    compression_latencies: std::vec::Vec<std::time::Duration>,
+    decompression_latencies: std::vec::Vec<std::time::Duration>,
+    compressed_size: usize,
+}
+
+/// Run `f`, turning a panic into an error.
+fn catch_panic<R>(f: impl FnOnce() -> anyhow::Result<R>) -> anyhow::Result<R> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow::Error::msg("scheme panicked")))
+}
+
+fn run_worker<C: Compressor + Decompressor + ?Sized>(
+    scheme: &C,
+    blocks: &[&[u8]],
+    barrier: &std::sync::Barrier,
+) -> anyhow::Result<Worker> {
+    // every worker has to reach every barrier, even if it failed or the scheme panicked, or the
+    // others would wait forever
+    barrier.wait();
+    let compression_start = std::time::Instant::now();
+    let compressed: anyhow::Result<std::vec::Vec<_>> = catch_panic(|| {
+        blocks
+            .iter()
+            .map(|block| crate::time(|| scheme.compress(block)))
+            .collect()
+    });
+    let compression_end = std::time::Instant::now();
+    barrier.wait();
+    let decompression_start = std::time::Instant::now();
+    let decompressed = match &compressed {
+        Ok(compressed) => catch_panic(|| {
+            compressed
+                .iter()
+                .zip(blocks)
+                .map(|((compressed, _), block)| {
+                    let mut decompressed = vec![0u8; block.len()];
+                    let ((), t) =
+                        crate::time(|| scheme.decompress_to(compressed, &mut decompressed))?;
+                    anyhow::ensure!(
+                        decompressed == *block,
+                        "CRITICAL BUG: decompress(compress(x)) != x"
+                    );
+                    Ok(t)
+                })
+                .collect()
+        }),
+        Err(_) => Ok(vec![]),
+    };
+    let decompression_end = std::time::Instant::now();
+    barrier.wait();
+
+    let compressed = compressed.context("couldn't time compression")?;
+    let decompression_latencies: std::vec::Vec<_> =
+        decompressed.context("couldn't time decompression")?;
+    Ok(Worker {
+        compression: compression_start..compression_end,
+        decompression: decompression_start..decompression_end,
+        compressed_size: compressed.iter().map(|(c, _)| c.len()).sum(),
+        compression_latencies: compressed.into_iter().map(|(_, t)| t).collect(),
+        decompression_latencies,
+    })
+}
+
+fn benchmark_scheme_concurrency<C: Compressor + Decompressor + DescribeScheme + Sync + ?Sized>(
+    scheme: &C,
+    corpus: &Corpus,
+    threads: std::num::NonZeroUsize,
+    block_size: usize,
+    samples: std::num::NonZeroU64,
+) -> anyhow::Result<ConcurrencyResult> {
+    let blocks: std::vec::Vec<&[u8]> = corpus.data.chunks(block_size).collect();
+    let per_thread: std::vec::Vec<std::vec::Vec<&[u8]>> = (0..threads.get())
+        .map(|k| {
+            blocks
+                .iter()
+                .skip(k)
+                .step_by(threads.get())
+                .copied()
+                .collect()
+        })
+        .collect();
+
+    let mut compressed_size = None;
+    let corpus_size_mb: f64 = corpus.data.len() as f64 / 1_000_000.0f64;
+    let mut compression_throughput_mbps = average::MeanWithError::new();
+    let mut decompression_throughput_mbps = average::MeanWithError::new();
+    let mut compression_latency_us = average::MeanWithError::new();
+    let mut decompression_latency_us = average::MeanWithError::new();
+    let mut compression_thread_time_us = average::MeanWithError::new();
+    let mut decompression_thread_time_us = average::MeanWithError::new();
+    let mut compression_thread_time_max_us = average::Mean::new();
+    let mut decompression_thread_time_max_us = average::Mean::new();
+    for _ in 0..samples.get() {
+        let barrier = std::sync::Barrier::new(threads.get());
+        let workers = std::thread::scope(|scope| {
+            per_thread
+                .iter()
+                .map(|blocks| scope.spawn(|| run_worker(scheme, blocks, &barrier)))
+                .collect::<std::vec::Vec<_>>()
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .map_err(|_| anyhow::Error::msg("worker thread panicked"))?
+                })
+                .collect::<anyhow::Result<std::vec::Vec<_>>>()
+        })?;
+        // the phases span from the first worker starting to the last worker finishing
+        let span = |phase: fn(&Worker) -> &std::ops::Range<std::time::Instant>| {
+            let start = workers.iter().map(|w| phase(w).start).min().unwrap();
+            let end = workers.iter().map(|w| phase(w).end).max().unwrap();
+            end - start
+        };
+        let compression_time = span(|w| &w.compression);
+        let decompression_time = span(|w| &w.decompression);
+
+        let size: usize = workers.iter().map(|w| w.compressed_size).sum();
+        if let Some(previous) = compressed_size {
+            anyhow::ensure!(previous == size, "compressed data size changed during runs");
+        } else {
+            compressed_size = Some(size);
+        }
+        compression_throughput_mbps.add(corpus_size_mb / compression_time.as_secs_f64());
+        decompression_throughput_mbps.add(corpus_size_mb / decompression_time.as_secs_f64());
+        // how long each thread took for its share, and the slowest thread
+        let us = |t: std::time::Duration| t.as_secs_f64() * 1_000_000.0f64;
+        for (times, max, phase) in [
+            (
+                &mut compression_thread_time_us,
+                &mut compression_thread_time_max_us,
+                (|w| &w.compression) as fn(&Worker) -> &std::ops::Range<std::time::Instant>,
+            ),
+            (
+                &mut decompression_thread_time_us,
+                &mut decompression_thread_time_max_us,
+                |w| &w.decompression,
+            ),
+        ] {
+            let thread_times: std::vec::Vec<f64> = workers
+                .iter()
+                .map(|w| us(phase(w).end - phase(w).start))
+                .collect();
+            for &t in thread_times.iter() {
+                times.add(t);
+            }
+            max.add(thread_times.iter().copied().fold(0.0, f64::max));
+        }
+        for worker in workers.iter() {
+            for t in worker.compression_latencies.iter() {
+                compression_latency_us.add(t.as_secs_f64() * 1_000_000.0f64);
+            }
+            for t in worker.decompression_latencies.iter() {
+                decompression_latency_us.add(t.as_secs_f64() * 1_000_000.0f64);
+            }
+        }
+    }
+
+    let compressed_size = compressed_size.expect("must be set because sample size is nonzero");
+
+    Ok(ConcurrencyResult {
+        scheme: scheme.name(),
+        settings: scheme.settings(),
+        corpus: corpus.name,
+        threads: threads.get(),
+        block_size,
+        compression_throughput: compression_throughput_mbps.mean(),
+        compression_throughput_std: compression_throughput_mbps.sample_variance().sqrt(),
+        decompression_throughput: decompression_throughput_mbps.mean(),
+        decompression_throughput_std: decompression_throughput_mbps.sample_variance().sqrt(),
+        compression_latency: compression_latency_us.mean(),
+        compression_latency_std: compression_latency_us.sample_variance().sqrt(),
+        decompression_latency: decompression_latency_us.mean(),
+        decompression_latency_std: decompression_latency_us.sample_variance().sqrt(),
+        compression_thread_time: compression_thread_time_us.mean(),
+        compression_thread_time_std: compression_thread_time_us.sample_variance().sqrt(),
+        compression_thread_time_max: compression_thread_time_max_us.mean(),
+        decompression_thread_time: decompression_thread_time_us.mean(),
+        decompression_thread_time_std: decompression_thread_time_us.sample_variance().sqrt(),
+        decompression_thread_time_max: decompression_thread_time_max_us.mean(),
+        compression_ratio: (corpus.data.len() as f64) / (compressed_size as f64),
+    })
+}
+
+/// Runs many independent streams at once: with K threads, each thread compresses and decompresses
+/// its own share of fixed-size blocks of the corpus.
+/// Reports the aggregate throughput of all threads, the latency per block and the time each thread
+/// takes for its share, for each K up to the number of available cores.
+pub fn benchmark_concurrency<
+    W: std::io::Write,
+    F: std::borrow::BorrowMut<W>,
+    C: Compressor + Decompressor + DescribeScheme + Sync + ?Sized,
+    S: std::borrow::Borrow<C>,
+    I: IntoIterator<Item = S>,
+>(
+    mut f: F,
+    schemes: I,
+) -> anyhow::Result<()> {
+    let corpora = crate::read_corpora().context("couldn't read corpora")?;
+    for scheme in schemes {
+        let scheme = scheme.borrow();
+        for corpus in corpora.iter() {
+            for block_size in BLOCK_SIZES {
+                for threads in crate::thread_counts() {
+                    let result = benchmark_scheme_concurrency(
+                        scheme,
+                        corpus,
+                        threads,
+                        block_size,
+                        crate::SAMPLES,
+                    )
+                    .with_context(|| {
+                        if let Some(settings) = scheme.settings() {
+                            format!(
+                                "benchmark failed for scheme {} (settings '{}') with corpus {} ({} threads, block size {})",
+                                scheme.name(),
+                                settings,
+                                corpus.name,
+                                threads,
+                                block_size
+                            )
+                        } else {
+                            format!(
+                                "benchmark failed for scheme {} with corpus {} ({} threads, block size {})",
+                                scheme.name(),
+                                corpus.name,
+                                threads,
+                                block_size
+                            )
+                        }
+                    })?;
+                    crate::print_row(f.borrow_mut(), result)
+                        .context("couldn't print result to stdout")?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Panics on the block that starts with a one, which only one of the threads gets.
+    struct PanicOnOne;
+
+    impl Compressor for PanicOnOne {
+        fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+            assert_ne!(data[0], 1, "compressing a one");
+            Ok(data.to_vec())
+        }
+    }
+
+    impl Decompressor for PanicOnOne {
+        fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+            dst.copy_from_slice(src);
+            Ok(())
+        }
+    }
+
+    impl DescribeScheme for PanicOnOne {
+        fn name(&self) -> String {
+            "panic on one".to_string()
+        }
+        fn settings(&self) -> Option<String> {
+            None
+        }
+    }
+
+    #[test]
+    fn panic_fails_instead_of_hanging() {
+        let corpus = Corpus {
+            name: "blocks",
+            data: (0..4u8).flat_map(|i| [i; 16]).collect(),
+        };
+        let threads = std::num::NonZeroUsize::new(2).unwrap();
+        let result =
+            benchmark_scheme_concurrency(&PanicOnOne, &corpus, threads, 16, crate::SAMPLES);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reports_thread_times() {
+        let corpus = Corpus {
+            name: "blocks",
+            data: (2..6u8).flat_map(|i| [i; 16]).collect(),
+        };
+        let threads = std::num::NonZeroUsize::new(2).unwrap();
+        let result =
+            benchmark_scheme_concurrency(&PanicOnOne, &corpus, threads, 16, crate::SAMPLES)
+                .unwrap();
+        assert_eq!(result.compression_ratio, 1.0);
+        assert!(result.compression_thread_time_max >= result.compression_thread_time);
+        assert!(result.decompression_thread_time_max >= result.decompression_thread_time);
+    }
+}
@@ -0,0 +1,331 @@
+//! Identifying the format of compressed data by its magic bytes and header structure, for data of
+//! unknown provenance.
+//!
+//! Only formats with a container are detected, raw streams (deflate, LZ4 blocks, raw snappy, ...)
+//! have no reliable signature. Schemes declare the format of their output with
+//! [`DescribeScheme::format`](crate::DescribeScheme::format), and `ccmp detect` checks the detector
+//! against the output of every scheme.
+
+use crate::Decompressor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Zlib,
+    /// A Zstandard frame, possibly preceded by skippable frames.
+    Zstd,
+    Xz,
+    /// The legacy `.lzma` format of LZMA Utils ("lzma alone"), with the 13-byte header.
+    LzmaAlone,
+    Bzip2,
+    /// The LZ4 frame format, written by lzzzz's `lz4f` and lz4_flex's `frame` modules and the
+    /// reference `lz4` tool, possibly preceded by skippable frames.
+    Lz4Frame,
+    /// The snappy framing format, starting with a stream identifier chunk.
+    SnappyFramed,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Format::Gzip => "gzip",
+            Format::Zlib => "zlib",
+            Format::Zstd => "zstd",
+            Format::Xz => "xz",
+            Format::LzmaAlone => "lzma alone",
+            Format::Bzip2 => "bzip2",
+            Format::Lz4Frame => "lz4 frame",
+            Format::SnappyFramed => "snappy framed",
+        })
+    }
+}
+
+const ZSTD_MAGIC: u32 = 0xfd2f_b528;
+const LZ4_FRAME_MAGIC: u32 = 0x184d_2204;
+// the low 4 bits are free, shared by the zstd and LZ4 frame formats
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184d_2a50;
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0];
+const SNAPPY_STREAM_IDENTIFIER: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+const BZIP2_BLOCK_MAGIC: &[u8] = &[0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+const BZIP2_END_MAGIC: &[u8] = &[0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+
+fn u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// The format of `data`, or `None` if it doesn't look like any of the formats.
+pub fn detect(data: &[u8]) -> Option<Format> {
+    let mut data = data;
+    // skippable frames can precede zstd and LZ4 frames
+    while u32_le(data, 0)? & 0xffff_fff0 == SKIPPABLE_FRAME_MAGIC {
+        let len = usize::try_from(u32_le(data, 4)?).ok()?;
+        data = data.get(8usize.checked_add(len)?..)?;
+    }
+    match u32_le(data, 0)? {
+        ZSTD_MAGIC if is_zstd_frame(data) => return Some(Format::Zstd),
+        LZ4_FRAME_MAGIC if is_lz4_frame(data) => return Some(Format::Lz4Frame),
+        _ => {}
+    }
+    [
+        (Format::Gzip, is_gzip as fn(&[u8]) -> bool),
+        (Format::Xz, is_xz),
+        (Format::Bzip2, is_bzip2),
+        (Format::SnappyFramed, is_snappy_framed),
+        (Format::Zlib, is_zlib),
+        // the weakest signature, so last
+        (Format::LzmaAlone, is_lzma_alone),
+    ]
+    .into_iter()
+    .find(|(_, is_format)| is_format(data))
+    .map(|(format, _)| format)
+}
+
+fn is_zstd_frame(data: &[u8]) -> bool {
+    // the frame header descriptor, whose bit 3 is reserved
+    data.get(4)
+        .is_some_and(|&descriptor| descriptor & 0x08 == 0)
+}
+
+fn is_lz4_frame(data: &[u8]) -> bool {
+    let (Some(&flags), Some(&block_descriptor)) = (data.get(4), data.get(5)) else {
+        return false;
+    };
+    // version 01, a reserved bit, and a block maximum size of 64 KiB to 4 MiB
+    flags >> 6 == 0b01
+        && flags & 0x02 == 0
+        && block_descriptor & 0x8f == 0
+        && (4..=7).contains(&(block_descriptor >> 4))
+}
+
+fn is_gzip(data: &[u8]) -> bool {
+    // a 10-byte header and an 8-byte trailer, deflate is the only compression method, and the top 3
+    // flag bits are reserved
+    data.len() >= 18 && data[..3] == [0x1f, 0x8b, 8] && data[3] & 0xe0 == 0
+}
+
+fn is_xz(data: &[u8]) -> bool {
+    // the stream flags are a zero byte and a byte with the check type in the low 4 bits
+    data.len() >= 12 && data.starts_with(XZ_MAGIC) && data[6] == 0 && data[7] & 0xf0 == 0
+}
+
+fn is_bzip2(data: &[u8]) -> bool {
+    // a block size of 100k to 900k, followed by a block or the end of the stream
+    data.len() >= 10
+        && data.starts_with(b"BZh")
+        && (b'1'..=b'9').contains(&data[3])
+        && (data[4..10] == *BZIP2_BLOCK_MAGIC || data[4..10] == *BZIP2_END_MAGIC)
+}
+
+fn is_snappy_framed(data: &[u8]) -> bool {
+    data.starts_with(SNAPPY_STREAM_IDENTIFIER)
+}
+
+fn is_zlib(data: &[u8]) -> bool {
+    // a 2-byte header, at least one byte of deflate data and a 4-byte adler-32 trailer
+    if data.len() < 7 {
+        return false;
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    // deflate with a window of at most 32 KiB, a header checksum and no preset dictionary (which
+    // only applications that know it can use), then a deflate block whose type isn't reserved
+    cmf & 0x0f == 8
+        && cmf >> 4 <= 7
+        && ((u16::from(cmf) << 8) | u16::from(flg)) % 31 == 0
+        && flg & 0x20 == 0
+        && (data[2] >> 1) & 0x03 != 0x03
+}
+
+fn is_lzma_alone(data: &[u8]) -> bool {
+    // 13 bytes of header and at least the 5 initial bytes of the range coder
+    if data.len() < 18 {
+        return false;
+    }
+    // the lc/lp/pb properties, the dictionary size and the uncompressed size (all ones if unknown)
+    let properties = data[0];
+    let dict_size = u32_le(data, 1).unwrap();
+    let uncompressed_size = u64::from_le_bytes(data[5..13].try_into().unwrap());
+    // like xz-utils, only accept dictionary sizes of 2^n or 2^n + 2^(n-1) of at least 4 KiB and
+    // uncompressed sizes below 256 GiB, which is what encoders write in practice
+    let dict_size_ok = dict_size >= 1 << 12
+        && (dict_size.is_power_of_two() || (dict_size >> dict_size.trailing_zeros()) == 0b11);
+    properties < 9 * 5 * 5
+        && dict_size_ok
+        && (uncompressed_size == u64::MAX || uncompressed_size < 1 << 38)
+        // the range coder always starts with a zero byte
+        && data[13] == 0
+}
+
+/// A decompressor for data in any of the registered formats, which dispatches to the decompressor
+/// of the detected format.
+pub struct Detector<D> {
+    decompressors: std::vec::Vec<(Format, D)>,
+}
+
+impl<D> Default for Detector<D> {
+    fn default() -> Self {
+        Self {
+            decompressors: vec![],
+        }
+    }
+}
+
+impl<D> Detector<D> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decompress data of `format` with `decompressor`, replacing any earlier one for `format`.
+    pub fn with(mut self, format: Format, decompressor: D) -> Self {
+        self.decompressors.retain(|(f, _)| *f != format);
+        self.decompressors.push((format, decompressor));
+        self
+    }
+
+    /// The detected format of `data` and the decompressor registered for it.
+    pub fn decompressor(&self, data: &[u8]) -> anyhow::Result<(Format, &D)> {
+        let format = detect(data).ok_or(anyhow::Error::msg("unknown format"))?;
+        let (_, decompressor) = self
+            .decompressors
+            .iter()
+            .find(|(f, _)| *f == format)
+            .ok_or_else(|| anyhow::Error::msg(format!("no decompressor for {format}")))?;
+        Ok((format, decompressor))
+    }
+}
+
+impl<D: Decompressor> Decompressor for Detector<D> {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let (format, decompressor) = self.decompressor(src)?;
+        decompressor
+            .decompress_to(src, dst)
+            .map_err(|e| e.context(format!("{format} decompression failed")))
+    }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let (format, decompressor) = self.decompressor(src)?;
+        decompressor
+            .decompress(src)
+            .map_err(|e| e.context(format!("{format} decompression failed")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the output of each tool for "hello hello hello hello\n"
+    // gzip -n
+    const GZIP: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9,
+        0x57, 0xc8, 0x40, 0x27, 0xb9, 0x00, 0x00, 0x88, 0x59, 0x0b, 0x18, 0x00, 0x00, 0x00,
+    ];
+    // zlib
+    const ZLIB: &[u8] = &[
+        0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0xb9, 0x00, 0x70, 0xbe,
+        0x08, 0xbb,
+    ];
+    // zstd
+    const ZSTD: &[u8] = &[
+        0x28, 0xb5, 0x2f, 0xfd, 0x04, 0x58, 0x6d, 0x00, 0x00, 0x38, 0x68, 0x65, 0x6c, 0x6c, 0x6f,
+        0x20, 0x0a, 0x01, 0x00, 0x99, 0x4b, 0x11, 0xa8, 0x7c, 0x2e, 0xa8,
+    ];
+    // xz
+    const XZ: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x04, 0xe6, 0xd6, 0xb4, 0x46, 0x04, 0xc0, 0x14,
+        0x18, 0x21, 0x01, 0x16, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2c, 0xf3,
+        0x46, 0x45, 0xe0, 0x00, 0x17, 0x00, 0x0c, 0x5d, 0x00, 0x34, 0x19, 0x49, 0xee, 0x8d, 0xe9,
+        0x56, 0x0a, 0xc1, 0x21, 0xb0, 0x00, 0x00, 0x6c, 0x14, 0xed, 0x1b, 0x3b, 0x68, 0xbb, 0x91,
+        0x00, 0x01, 0x30, 0x18, 0x8e, 0x1b, 0xac, 0xec, 0x1f, 0xb6, 0xf3, 0x7d, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x04, 0x59, 0x5a,
+    ];
+    // xz --format=lzma
+    const LZMA_ALONE: &[u8] = &[
+        0x5d, 0x00, 0x00, 0x80, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x34,
+        0x19, 0x49, 0xee, 0x8d, 0xe9, 0x56, 0x0a, 0xc1, 0xb6, 0x20, 0xb7, 0xff, 0xff, 0xba, 0x34,
+        0x00, 0x00,
+    ];
+    // bzip2
+    const BZIP2: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x6f, 0x4f, 0x10, 0xf3, 0x00,
+        0x00, 0x05, 0xd1, 0x00, 0x00, 0x10, 0x40, 0x00, 0x02, 0x44, 0xa0, 0x00, 0x30, 0xc0, 0x02,
+        0xa8, 0x34, 0x71, 0x0d, 0xad, 0x87, 0x0f, 0x17, 0x72, 0x45, 0x38, 0x50, 0x90, 0x6f, 0x4f,
+        0x10, 0xf3,
+    ];
+    // lz4
+    const LZ4_FRAME: &[u8] = &[
+        0x04, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0x0f, 0x00, 0x00, 0x00, 0x69, 0x68, 0x65, 0x6c,
+        0x6c, 0x6f, 0x20, 0x06, 0x00, 0x50, 0x65, 0x6c, 0x6c, 0x6f, 0x0a, 0x00, 0x00, 0x00, 0x00,
+        0xe2, 0xff, 0x03, 0x42,
+    ];
+    // snap::write::FrameEncoder
+    const SNAPPY_FRAMED: &[u8] = &[
+        0xff, 0x06, 0x00, 0x00, 0x73, 0x4e, 0x61, 0x50, 0x70, 0x59, 0x00, 0x11, 0x00, 0x00, 0xfb,
+        0xe4, 0xec, 0xa5, 0x18, 0x14, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x42, 0x06, 0x00, 0x00,
+        0x0a,
+    ];
+    // zlib with negative window bits
+    const DEFLATE: &[u8] = &[
+        0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0xb9, 0x00,
+    ];
+    // lz4_flex::block::compress
+    const LZ4_BLOCK: &[u8] = &[
+        0x68, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x06, 0x00, 0x60, 0x68, 0x65, 0x6c, 0x6c, 0x6f,
+        0x0a,
+    ];
+
+    fn skippable_frame(payload: &[u8]) -> std::vec::Vec<u8> {
+        let mut frame = (SKIPPABLE_FRAME_MAGIC | 0x7).to_le_bytes().to_vec();
+        frame.extend((payload.len() as u32).to_le_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    #[test]
+    fn detects_formats() {
+        for (data, format) in [
+            (GZIP, Format::Gzip),
+            (ZLIB, Format::Zlib),
+            (ZSTD, Format::Zstd),
+            (XZ, Format::Xz),
+            (LZMA_ALONE, Format::LzmaAlone),
+            (BZIP2, Format::Bzip2),
+            (LZ4_FRAME, Format::Lz4Frame),
+            (SNAPPY_FRAMED, Format::SnappyFramed),
+        ] {
+            assert_eq!(detect(data), Some(format), "{format}");
+        }
+    }
+
+    #[test]
+    fn skips_skippable_frames() {
+        for (data, format) in [(ZSTD, Format::Zstd), (LZ4_FRAME, Format::Lz4Frame)] {
+            let mut framed = skippable_frame(b"metadata");
+            framed.extend(skippable_frame(&[]));
+            framed.extend(data);
+            assert_eq!(detect(&framed), Some(format), "{format}");
+        }
+        // a skippable frame on its own, or one that claims more data than there is
+        assert_eq!(detect(&skippable_frame(b"metadata")), None);
+        let mut truncated = skippable_frame(b"metadata");
+        truncated.truncate(12);
+        assert_eq!(detect(&truncated), None);
+    }
+
+    #[test]
+    fn rejects_raw_streams() {
+        assert_eq!(detect(DEFLATE), None);
+        assert_eq!(detect(LZ4_BLOCK), None);
+        assert_eq!(detect(&[]), None);
+        for seed in 0..64 {
+            let data = crate::synthetic::uniform_random(1 << 12, seed);
+            assert_eq!(detect(&data), None, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        for data in [GZIP, ZLIB, XZ, LZMA_ALONE, BZIP2] {
+            assert_eq!(detect(&data[..4]), None);
+        }
+    }
+}
@@ -2,10 +2,31 @@ use anyhow::Context as _;
 use average::Estimate as _;
 use std::borrow::BorrowMut as _;
 
+pub mod ccmp;
+mod chunked;
+mod command;
+mod concurrency;
+pub mod detect;
+mod parallel;
+pub mod plugin;
+pub mod probe;
+pub mod synthetic;
+
+pub use chunked::Chunked;
+pub use command::ExternalCommand;
+pub use concurrency::{benchmark_concurrency, ConcurrencyResult};
+pub use parallel::thread_counts;
+
 pub const SAMPLES: std::num::NonZeroU64 = match std::num::NonZeroU64::new(10) {
     Some(v) => v,
-    None => [][0],
-}; // see https://stackoverflow.com/a/66838483 for the source of this monstrosity
+    None => panic!("SAMPLES must be nonzero"),
+};
+
+/// If set, the `benchmark` functions only measure compression ratios: every scheme compresses
+/// every corpus once, in parallel on all cores, and the speeds in the results are empty.
+pub const RATIO_ONLY_VAR: &str = "BENCHMARK_RATIO_ONLY";
+/// Caps the number of threads with [`RATIO_ONLY_VAR`], which is the number of cores by default.
+pub const RATIO_THREADS_VAR: &str = "BENCHMARK_RATIO_THREADS";
 
 pub trait Compressor {
     fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>>;
@@ -13,11 +34,44 @@ pub trait Compressor {
 
 pub trait Decompressor {
     fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()>;
+    /// Decompress `src` without knowing the decompressed length, for data of unknown provenance
+    /// (see [`detect`]). Only schemes whose format records the end of the data support it.
+    fn decompress(&self, _src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        anyhow::bail!("decompression needs the decompressed length")
+    }
+}
+
+impl<T: Decompressor + ?Sized> Decompressor for &T {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        (**self).decompress_to(src, dst)
+    }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        (**self).decompress(src)
+    }
+}
+
+impl<T: Decompressor + ?Sized> Decompressor for Box<T> {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        (**self).decompress_to(src, dst)
+    }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        (**self).decompress(src)
+    }
 }
 
 pub trait DescribeScheme {
     fn name(&self) -> String;
     fn settings(&self) -> Option<String>;
+    /// The container format of the compressed data if [`detect::detect`] can identify it, `None`
+    /// for raw streams and formats it doesn't know.
+    fn format(&self) -> Option<detect::Format> {
+        None
+    }
+    /// The number of threads the scheme compresses or decompresses with, for schemes with native
+    /// multi-threading or [`Chunked`].
+    fn threads(&self) -> std::num::NonZeroUsize {
+        std::num::NonZeroUsize::MIN
+    }
 }
 
 pub struct Corpus {
@@ -40,77 +94,78 @@ pub fn read_corpus_data<P: AsRef<std::path::Path>, I: IntoIterator<Item = P>>(
     Ok(result)
 }
 
-pub fn read_corpora() -> anyhow::Result<std::vec::Vec<Corpus>> {
-    let base = "../../corpora/";
-    let canterbury = Corpus {
-        name: "canterbury",
-        data: read_corpus_data(
-            base,
-            [
-                "canterbury/alice29.txt",
-                "canterbury/asyoulik.txt",
-                "canterbury/cp.html",
-                "canterbury/fields.c",
-                "canterbury/grammar.lsp",
-                "canterbury/kennedy.xls",
-                "canterbury/lcet10.txt",
-                "canterbury/plrabn12.txt",
-                "canterbury/ptt5",
-                "canterbury/sum",
-                "canterbury/xargs.1",
-            ],
-        )
-        .context("couldn't read canterbury corpus")?,
-    };
-    anyhow::ensure!(
-        canterbury.data.len() == 2_810_784,
-        "canterbury corpus has unexpected size"
-    );
+pub const CORPORA_BASE: &str = "../../corpora/";
 
-    let canterbury_large = Corpus {
-        name: "canterbury large",
-        data: read_corpus_data(
-            base,
-            [
-                "canterbury-large/E.coli",
-                "canterbury-large/bible.txt",
-                "canterbury-large/world192.txt",
-            ],
-        )
-        .context("couldn't read canterbury large corpus")?,
-    };
-    anyhow::ensure!(
-        canterbury_large.data.len() == 11_159_482,
-        "canterbury large corpus has unexpected size"
-    );
+pub struct CorpusFiles {
+    pub name: &'static str,
+    pub files: &'static [&'static str],
+    pub size: usize,
+}
 
-    let silesia = Corpus {
+pub const CORPUS_FILES: [CorpusFiles; 3] = [
+    CorpusFiles {
+        name: "canterbury",
+        files: &[
+            "canterbury/alice29.txt",
+            "canterbury/asyoulik.txt",
+            "canterbury/cp.html",
+            "canterbury/fields.c",
+            "canterbury/grammar.lsp",
+            "canterbury/kennedy.xls",
+            "canterbury/lcet10.txt",
+            "canterbury/plrabn12.txt",
+            "canterbury/ptt5",
+            "canterbury/sum",
+            "canterbury/xargs.1",
+        ],
+        size: 2_810_784,
+    },
+    CorpusFiles {
+        name: "canterbury large",
+        files: &[
+            "canterbury-large/E.coli",
+            "canterbury-large/bible.txt",
+            "canterbury-large/world192.txt",
+        ],
+        size: 11_159_482,
+    },
+    CorpusFiles {
         name: "silesia",
-        data: read_corpus_data(
-            base,
-            [
-                "silesia/dickens",
-                "silesia/mozilla",
-                "silesia/mr",
-                "silesia/nci",
-                "silesia/ooffice",
-                "silesia/osdb",
-                "silesia/reymont",
-                "silesia/samba",
-                "silesia/sao",
-                "silesia/webster",
-                "silesia/xml",
-                "silesia/x-ray",
-            ],
-        )
-        .context("couldn't read silesia corpus")?,
-    };
-    anyhow::ensure!(
-        silesia.data.len() == 211_938_580,
-        "silesia corpus has unexpected size"
-    );
+        files: &[
+            "silesia/dickens",
+            "silesia/mozilla",
+            "silesia/mr",
+            "silesia/nci",
+            "silesia/ooffice",
+            "silesia/osdb",
+            "silesia/reymont",
+            "silesia/samba",
+            "silesia/sao",
+            "silesia/webster",
+            "silesia/xml",
+            "silesia/x-ray",
+        ],
+        size: 211_938_580,
+    },
+];
 
-    Ok(vec![canterbury, canterbury_large, silesia])
+pub fn read_corpora() -> anyhow::Result<std::vec::Vec<Corpus>> {
+    let mut corpora = vec![];
+    for corpus in CORPUS_FILES.iter() {
+        let data = read_corpus_data(CORPORA_BASE, corpus.files.iter().copied())
+            .with_context(|| format!("couldn't read {} corpus", corpus.name))?;
+        anyhow::ensure!(
+            data.len() == corpus.size,
+            "{} corpus has unexpected size",
+            corpus.name
+        );
+        corpora.push(Corpus {
+            name: corpus.name,
+            data,
+        });
+    }
+    corpora.extend(synthetic::corpora());
+    Ok(corpora)
 }
 
 #[derive(serde::Serialize)]
@@ -118,8 +173,8 @@ pub struct Result {
     pub scheme: String,
     pub settings: Option<String>,
     pub corpus: &'static str,
-    pub compression_speed: f64,
-    pub compression_speed_std: f64,
+    pub compression_speed: Option<f64>,
+    pub compression_speed_std: Option<f64>,
     pub decompression_speed: Option<f64>,
     pub decompression_speed_std: Option<f64>,
     pub compression_ratio: f64,
@@ -129,11 +184,15 @@ pub fn print_result<W: std::io::Write, R: std::borrow::Borrow<Result>>(
     f: &mut W,
     result: R,
 ) -> anyhow::Result<()> {
+    print_row(f, result.borrow())
+}
+
+fn print_row<W: std::io::Write, T: serde::Serialize>(f: &mut W, row: T) -> anyhow::Result<()> {
     let mut writer = csv::WriterBuilder::new()
         .quote_style(csv::QuoteStyle::Necessary)
         .has_headers(false)
         .from_writer(f.borrow_mut());
-    writer.serialize(result.borrow())?;
+    writer.serialize(row)?;
     Ok(())
 }
 
@@ -147,6 +206,131 @@ where
     Ok((result, duration))
 }
 
+fn ratio_result<C: DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &Corpus,
+    compressed_size: usize,
+) -> Result {
+    Result {
+        scheme: scheme.name(),
+        settings: scheme.settings(),
+        corpus: corpus.name,
+        compression_speed: None,
+        compression_speed_std: None,
+        decompression_speed: None,
+        decompression_speed_std: None,
+        compression_ratio: (corpus.data.len() as f64) / (compressed_size as f64),
+    }
+}
+
+fn ratio_scheme<C: Compressor + Decompressor + DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &Corpus,
+) -> anyhow::Result<Result> {
+    let compressed = scheme
+        .compress(&corpus.data)
+        .context("couldn't compress corpus")?;
+    let mut decompressed = vec![0u8; corpus.data.len()];
+    scheme
+        .decompress_to(&compressed, &mut decompressed[..])
+        .context("couldn't decompress corpus")?;
+    anyhow::ensure!(
+        decompressed == corpus.data,
+        "CRITICAL BUG: decompress(compress(x)) != x"
+    );
+    Ok(ratio_result(scheme, corpus, compressed.len()))
+}
+
+fn ratio_compression_scheme<C: Compressor + DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &Corpus,
+) -> anyhow::Result<Result> {
+    let compressed = scheme
+        .compress(&corpus.data)
+        .context("couldn't compress corpus")?;
+    Ok(ratio_result(scheme, corpus, compressed.len()))
+}
+
+fn ratio_threads() -> anyhow::Result<std::num::NonZeroUsize> {
+    let cores = std::thread::available_parallelism().unwrap_or(std::num::NonZeroUsize::MIN);
+    match std::env::var(RATIO_THREADS_VAR) {
+        Ok(threads) => Ok(threads
+            .parse::<std::num::NonZeroUsize>()
+            .with_context(|| format!("invalid {RATIO_THREADS_VAR}"))?
+            .min(cores)),
+        Err(std::env::VarError::NotPresent) => Ok(cores),
+        Err(e) => Err(e).with_context(|| format!("invalid {RATIO_THREADS_VAR}")),
+    }
+}
+
+/// Measure `ratio` for every scheme and corpus in parallel on all cores, timings would be
+/// meaningless, and return the results in the usual order. Multi-threaded schemes run one job at a
+/// time afterwards, so that they don't compete with each other for the cores.
+pub(crate) fn ratios<C: DescribeScheme + ?Sized, S: std::borrow::Borrow<C> + Sync>(
+    schemes: &[S],
+    corpora: &[Corpus],
+    ratio: fn(&C, &Corpus) -> anyhow::Result<Result>,
+) -> anyhow::Result<std::vec::Vec<Result>> {
+    let (exclusive, shared): (std::vec::Vec<_>, std::vec::Vec<_>) = (0..schemes.len())
+        .flat_map(|scheme| (0..corpora.len()).map(move |corpus| (scheme, corpus)))
+        .partition(|&(scheme, _)| schemes[scheme].borrow().threads().get() > 1);
+    let job = |(scheme, corpus): (usize, usize)| {
+        let (scheme, corpus) = (schemes[scheme].borrow(), &corpora[corpus]);
+        ratio(scheme, corpus).with_context(|| {
+            if let Some(settings) = scheme.settings() {
+                format!(
+                    "benchmark failed for scheme {} (settings '{}') with corpus {}",
+                    scheme.name(),
+                    settings,
+                    corpus.name
+                )
+            } else {
+                format!(
+                    "benchmark failed for scheme {} with corpus {}",
+                    scheme.name(),
+                    corpus.name
+                )
+            }
+        })
+    };
+    let mut results: std::vec::Vec<_> = shared
+        .iter()
+        .copied()
+        .zip(parallel::map(ratio_threads()?, shared.clone(), job)?)
+        .collect();
+    results.extend(exclusive.iter().copied().zip(parallel::map(
+        std::num::NonZeroUsize::MIN,
+        exclusive.clone(),
+        job,
+    )?));
+    results.sort_by_key(|&(job, _)| job);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Print the [`ratios`] of every scheme on the corpora.
+fn benchmark_ratios<
+    W: std::io::Write,
+    F: std::borrow::BorrowMut<W>,
+    C: DescribeScheme + ?Sized,
+    S: std::borrow::Borrow<C> + Sync,
+    I: IntoIterator<Item = S>,
+>(
+    mut f: F,
+    schemes: I,
+    ratio: fn(&C, &Corpus) -> anyhow::Result<Result>,
+) -> anyhow::Result<()> {
+    let corpora = read_corpora().context("couldn't read corpora")?;
+    let schemes: std::vec::Vec<S> = schemes.into_iter().collect();
+    for result in ratios(&schemes, &corpora, ratio)? {
+        print_result(f.borrow_mut(), result).context("couldn't print result to stdout")?;
+    }
+    Ok(())
+}
+
+fn ratio_only() -> bool {
+    std::env::var_os(RATIO_ONLY_VAR).is_some()
+}
+
 fn benchmark_scheme<C: Compressor + Decompressor + DescribeScheme + ?Sized>(
     scheme: &C,
     corpus: &Corpus,
@@ -187,8 +371,8 @@ fn benchmark_scheme<C: Compressor + Decompressor + DescribeScheme + ?Sized>(
         scheme: scheme.name(),
         settings: scheme.settings(),
         corpus: corpus.name,
-        compression_speed: compression_speed_mbps.mean(),
-        compression_speed_std: compression_speed_mbps.sample_variance().sqrt(),
+        compression_speed: Some(compression_speed_mbps.mean()),
+        compression_speed_std: Some(compression_speed_mbps.sample_variance().sqrt()),
         decompression_speed: Some(decompression_speed_mbps.mean()),
         decompression_speed_std: Some(decompression_speed_mbps.sample_variance().sqrt()),
         compression_ratio: (corpus.data.len() as f64) / (compressed_size as f64),
@@ -199,39 +383,44 @@ pub fn benchmark<
     W: std::io::Write,
     F: std::borrow::BorrowMut<W>,
     C: Compressor + Decompressor + DescribeScheme + ?Sized,
-    S: std::borrow::Borrow<C>,
+    S: std::borrow::Borrow<C> + Sync,
     I: IntoIterator<Item = S>,
 >(
     mut f: F,
     schemes: I,
 ) -> anyhow::Result<()> {
+    if let Some(request) = ccmp::Request::from_env().context("invalid ccmp request")? {
+        return request.handle::<C, _, _>(schemes, ccmp::Timing::Both);
+    }
+    if ratio_only() {
+        return benchmark_ratios::<W, _, C, _, _>(f, schemes, ratio_scheme);
+    }
     let corpora = read_corpora().context("couldn't read corpora")?;
     for scheme in schemes {
         for corpus in corpora.iter() {
-            let result =
-                benchmark_scheme(scheme.borrow(), &corpus, SAMPLES).with_context(|| {
-                    if let Some(settings) = scheme.borrow().settings() {
-                        format!(
-                            "benchmark failed for scheme {} (settings '{}') with corpus {}",
-                            scheme.borrow().name(),
-                            settings,
-                            corpus.name
-                        )
-                    } else {
-                        format!(
-                            "benchmark failed for scheme {} with corpus {}",
-                            scheme.borrow().name(),
-                            corpus.name
-                        )
-                    }
-                })?;
+            let result = benchmark_scheme(scheme.borrow(), corpus, SAMPLES).with_context(|| {
+                if let Some(settings) = scheme.borrow().settings() {
+                    format!(
+                        "benchmark failed for scheme {} (settings '{}') with corpus {}",
+                        scheme.borrow().name(),
+                        settings,
+                        corpus.name
+                    )
+                } else {
+                    format!(
+                        "benchmark failed for scheme {} with corpus {}",
+                        scheme.borrow().name(),
+                        corpus.name
+                    )
+                }
+            })?;
             print_result(f.borrow_mut(), result).context("couldn't print result to stdout")?;
         }
     }
     Ok(())
 }
 
-fn benchmark_compression_scheme<C: Compressor + DescribeScheme>(
+fn benchmark_compression_scheme<C: Compressor + DescribeScheme + ?Sized>(
     scheme: &C,
     corpus: &Corpus,
     samples: std::num::NonZeroU64,
@@ -260,8 +449,8 @@ fn benchmark_compression_scheme<C: Compressor + DescribeScheme>(
         scheme: scheme.name(),
         settings: scheme.settings(),
         corpus: corpus.name,
-        compression_speed: compression_speed_mbps.mean(),
-        compression_speed_std: compression_speed_mbps.sample_variance().sqrt(),
+        compression_speed: Some(compression_speed_mbps.mean()),
+        compression_speed_std: Some(compression_speed_mbps.sample_variance().sqrt()),
         decompression_speed: None,
         decompression_speed_std: None,
         compression_ratio: (corpus.data.len() as f64) / (compressed_size as f64),
@@ -272,16 +461,100 @@ pub fn benchmark_compression_only<
     W: std::io::Write,
     F: std::borrow::BorrowMut<W>,
     C: Compressor + DescribeScheme,
-    S: std::borrow::Borrow<C>,
+    S: std::borrow::Borrow<C> + Sync,
     I: IntoIterator<Item = S>,
 >(
     mut f: F,
     schemes: I,
 ) -> anyhow::Result<()> {
+    if let Some(request) = ccmp::Request::from_env().context("invalid ccmp request")? {
+        return request.handle_compression_only::<C, _, _>(schemes);
+    }
+    if ratio_only() {
+        return benchmark_ratios::<W, _, C, _, _>(f, schemes, ratio_compression_scheme);
+    }
+    let corpora = read_corpora().context("couldn't read corpora")?;
+    for scheme in schemes {
+        for corpus in corpora.iter() {
+            let result = benchmark_compression_scheme(scheme.borrow(), corpus, SAMPLES)
+                .with_context(|| {
+                    if let Some(settings) = scheme.borrow().settings() {
+                        format!(
+                            "benchmark failed for scheme {} (settings '{}') with corpus {}",
+                            scheme.borrow().name(),
+                            settings,
+                            corpus.name
+                        )
+                    } else {
+                        format!(
+                            "benchmark failed for scheme {} with corpus {}",
+                            scheme.borrow().name(),
+                            corpus.name
+                        )
+                    }
+                })?;
+            print_result(f.borrow_mut(), result).context("couldn't print result to stdout")?;
+        }
+    }
+    Ok(())
+}
+
+fn benchmark_decompression_scheme<C: Compressor + Decompressor + DescribeScheme + ?Sized>(
+    scheme: &C,
+    corpus: &Corpus,
+    samples: std::num::NonZeroU64,
+) -> anyhow::Result<Result> {
+    let corpus_size_mb: f64 = corpus.data.len() as f64 / 1_000_000.0f64;
+    let mut decompression_speed_mbps = average::MeanWithError::new();
+    let compressed = scheme
+        .compress(&corpus.data)
+        .context("couldn't compress corpus")?;
+    for _ in 0..samples.get() {
+        // decompress
+        let mut decompressed = vec![0u8; corpus.data.len()];
+        let ((), t) = time(|| scheme.decompress_to(&compressed, &mut decompressed[..]))
+            .context("couldn't time decompression")?;
+        anyhow::ensure!(
+            decompressed == corpus.data,
+            "CRITICAL BUG: decompress(compress(x)) != x"
+        );
+        decompression_speed_mbps.add(corpus_size_mb / t.as_secs_f64());
+    }
+
+    Ok(Result {
+        scheme: scheme.name(),
+        settings: scheme.settings(),
+        corpus: corpus.name,
+        compression_speed: None,
+        compression_speed_std: None,
+        decompression_speed: Some(decompression_speed_mbps.mean()),
+        decompression_speed_std: Some(decompression_speed_mbps.sample_variance().sqrt()),
+        compression_ratio: (corpus.data.len() as f64) / (compressed.len() as f64),
+    })
+}
+
+/// For decoders without a matching encoder: `compress` only prepares the input for `decompress_to`
+/// (e.g. with another implementation of the same format) and isn't timed.
+pub fn benchmark_decompression_only<
+    W: std::io::Write,
+    F: std::borrow::BorrowMut<W>,
+    C: Compressor + Decompressor + DescribeScheme,
+    S: std::borrow::Borrow<C> + Sync,
+    I: IntoIterator<Item = S>,
+>(
+    mut f: F,
+    schemes: I,
+) -> anyhow::Result<()> {
+    if let Some(request) = ccmp::Request::from_env().context("invalid ccmp request")? {
+        return request.handle::<C, _, _>(schemes, ccmp::Timing::DecompressionOnly);
+    }
+    if ratio_only() {
+        return benchmark_ratios::<W, _, C, _, _>(f, schemes, ratio_scheme);
+    }
     let corpora = read_corpora().context("couldn't read corpora")?;
     for scheme in schemes {
         for corpus in corpora.iter() {
-            let result = benchmark_compression_scheme(scheme.borrow(), &corpus, SAMPLES)
+            let result = benchmark_decompression_scheme(scheme.borrow(), corpus, SAMPLES)
                 .with_context(|| {
                     if let Some(settings) = scheme.borrow().settings() {
                         format!(
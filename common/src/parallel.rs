@@ -0,0 +1,98 @@
+/// Applies `f` to every item on up to `threads` scoped worker threads.
+///
+/// Workers pull items from a shared queue, so uneven work is balanced between them.
+/// The results are returned in the order of the input items. After the first error, the workers
+/// stop pulling items and the error is returned.
+pub(crate) fn map<T, R, F>(
+    threads: std::num::NonZeroUsize,
+    items: std::vec::Vec<T>,
+    f: F,
+) -> anyhow::Result<std::vec::Vec<R>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> anyhow::Result<R> + Sync,
+{
+    let len = items.len();
+    let queue = std::sync::Mutex::new(items.into_iter().enumerate());
+    let results = std::sync::Mutex::new(std::iter::repeat_with(|| None).take(len).collect());
+    let failed = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|scope| -> anyhow::Result<()> {
+        let workers: std::vec::Vec<_> = (0..threads.get().min(len))
+            .map(|_| {
+                scope.spawn(|| -> anyhow::Result<()> {
+                    loop {
+                        if failed.load(std::sync::atomic::Ordering::Relaxed) {
+                            return Ok(());
+                        }
+                        let next = queue.lock().expect("work queue poisoned").next();
+                        let Some((index, item)) = next else {
+                            return Ok(());
+                        };
+                        let result = f(item).inspect_err(|_| {
+                            failed.store(true, std::sync::atomic::Ordering::Relaxed);
+                        })?;
+                        let results: &mut std::vec::Vec<Option<R>> =
+                            &mut results.lock().expect("result list poisoned");
+                        results[index] = Some(result);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker
+                .join()
+                .map_err(|_| anyhow::Error::msg("worker thread panicked"))??;
+        }
+        Ok(())
+    })?;
+    Ok(results
+        .into_inner()
+        .expect("result list poisoned")
+        .into_iter()
+        .map(|result: Option<R>| result.expect("all items must have been processed"))
+        .collect())
+}
+
+/// Thread counts to test: powers of two up to the number of available cores, and that number itself.
+pub fn thread_counts() -> std::vec::Vec<std::num::NonZeroUsize> {
+    let max = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+    let mut counts: std::vec::Vec<_> = std::iter::successors(Some(1usize), |n| n.checked_mul(2))
+        .take_while(|&n| n < max)
+        .collect();
+    counts.push(max);
+    counts
+        .into_iter()
+        .map(|n| std::num::NonZeroUsize::new(n).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_order() {
+        let threads = std::num::NonZeroUsize::new(4).unwrap();
+        let squares = map(threads, (0..100).collect(), |i: u64| Ok(i * i)).unwrap();
+        assert_eq!(
+            squares,
+            (0..100).map(|i| i * i).collect::<std::vec::Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let threads = std::num::NonZeroUsize::new(2).unwrap();
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let result = map(threads, (0..1000).collect(), |i: usize| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            anyhow::ensure!(i != 0, "failed");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            Ok(i)
+        });
+        assert!(result.is_err());
+        // the other worker can finish the item it's working on
+        assert!(calls.load(std::sync::atomic::Ordering::Relaxed) <= threads.get() + 1);
+    }
+}
@@ -0,0 +1,186 @@
+//! Codecs loaded at runtime from a dynamic library, for codecs that can't be added to this
+//! repository.
+//!
+//! A plugin exports two functions with C linkage:
+//! - `uint32_t compression_plugin_abi_version(void)`, which returns [`PLUGIN_ABI_VERSION`]
+//! - `const PluginCodec *compression_plugin_codecs(size_t *count)`, which returns an array of
+//!   `*count` codecs (e.g. one per compression level) that stays valid while the library is loaded
+//!
+//! `plugin.h` in this crate has the equivalent C declarations.
+//! All functions of a codec have to be thread-safe, they may be called concurrently.
+
+use crate::{Compressor, Decompressor, DescribeScheme};
+use anyhow::Context as _;
+use std::ffi::{c_char, c_int, CStr};
+
+/// Bumped on every incompatible change of the types below.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+pub const ABI_VERSION_SYMBOL: &[u8] = b"compression_plugin_abi_version\0";
+pub const CODECS_SYMBOL: &[u8] = b"compression_plugin_codecs\0";
+
+/// NUL-terminated UTF-8 strings that stay valid while the library is loaded.
+#[repr(C)]
+pub struct PluginDescription {
+    pub name: *const c_char,
+    /// May be null if the codec has no settings.
+    pub settings: *const c_char,
+}
+
+#[repr(C)]
+pub struct PluginCodec {
+    /// Passed unchanged to every function, e.g. an index or a pointer to the codec's state.
+    pub context: usize,
+    pub describe: unsafe extern "C" fn(context: usize) -> PluginDescription,
+    /// An upper bound for the compressed size of `src_len` bytes.
+    pub compress_bound: unsafe extern "C" fn(context: usize, src_len: usize) -> usize,
+    /// Compresses `src` into `dst`, which has room for `compress_bound(src_len)` bytes, and
+    /// stores the compressed size in `dst_len`.
+    /// Returns 0 on success and an error code otherwise.
+    pub compress: unsafe extern "C" fn(
+        context: usize,
+        src: *const u8,
+        src_len: usize,
+        dst: *mut u8,
+        dst_capacity: usize,
+        dst_len: *mut usize,
+    ) -> c_int,
+    /// Decompresses `src` into `dst`.
+    /// Returns 0 only if the decompressed data is exactly `dst_len` bytes long.
+    pub decompress: unsafe extern "C" fn(
+        context: usize,
+        src: *const u8,
+        src_len: usize,
+        dst: *mut u8,
+        dst_len: usize,
+    ) -> c_int,
+}
+
+pub type AbiVersionFn = unsafe extern "C" fn() -> u32;
+pub type CodecsFn = unsafe extern "C" fn(count: *mut usize) -> *const PluginCodec;
+
+/// A codec of a loaded plugin, which keeps the library loaded.
+pub struct PluginScheme {
+    codec: *const PluginCodec,
+    name: String,
+    settings: Option<String>,
+    _library: std::sync::Arc<libloading::Library>,
+}
+
+// the plugin ABI requires thread-safe codecs, and the codec array outlives the scheme because the
+// scheme keeps the library loaded
+unsafe impl Send for PluginScheme {}
+unsafe impl Sync for PluginScheme {}
+
+unsafe fn c_string(ptr: *const c_char) -> anyhow::Result<Option<String>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    let string = CStr::from_ptr(ptr)
+        .to_str()
+        .context("plugin returned a string that isn't UTF-8")?;
+    Ok(Some(string.to_string()))
+}
+
+/// Load all codecs of the plugin at `path`.
+pub fn load_plugin<P: AsRef<std::ffi::OsStr>>(
+    path: P,
+) -> anyhow::Result<std::vec::Vec<PluginScheme>> {
+    let path = path.as_ref();
+    // loading a library runs its initializers, so this is only as safe as the plugin
+    let library = unsafe { libloading::Library::new(path) }
+        .with_context(|| format!("couldn't load plugin {}", path.to_string_lossy()))?;
+    let library = std::sync::Arc::new(library);
+    let (codecs, count) = unsafe {
+        let abi_version: libloading::Symbol<AbiVersionFn> = library
+            .get(ABI_VERSION_SYMBOL)
+            .context("plugin doesn't export compression_plugin_abi_version")?;
+        let abi_version = abi_version();
+        anyhow::ensure!(
+            abi_version == PLUGIN_ABI_VERSION,
+            "plugin ABI version {abi_version} doesn't match {PLUGIN_ABI_VERSION}"
+        );
+        let codecs: libloading::Symbol<CodecsFn> = library
+            .get(CODECS_SYMBOL)
+            .context("plugin doesn't export compression_plugin_codecs")?;
+        let mut count = 0;
+        let codecs = codecs(&mut count);
+        (codecs, count)
+    };
+    anyhow::ensure!(
+        count == 0 || !codecs.is_null(),
+        "plugin returned a null codec array"
+    );
+    let mut schemes = std::vec::Vec::with_capacity(count);
+    for i in 0..count {
+        let codec = unsafe { codecs.add(i) };
+        let description = unsafe { ((*codec).describe)((*codec).context) };
+        let name = unsafe { c_string(description.name) }?.context("plugin codec has no name")?;
+        let settings = unsafe { c_string(description.settings) }?;
+        schemes.push(PluginScheme {
+            codec,
+            name,
+            settings,
+            _library: library.clone(),
+        });
+    }
+    Ok(schemes)
+}
+
+impl DescribeScheme for PluginScheme {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn settings(&self) -> Option<String> {
+        self.settings.clone()
+    }
+}
+
+impl Compressor for PluginScheme {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let codec = unsafe { &*self.codec };
+        let bound = unsafe { (codec.compress_bound)(codec.context, data.len()) };
+        let mut vec = vec![0; bound];
+        let mut len = 0;
+        let code = unsafe {
+            (codec.compress)(
+                codec.context,
+                data.as_ptr(),
+                data.len(),
+                vec.as_mut_ptr(),
+                vec.len(),
+                &mut len,
+            )
+        };
+        anyhow::ensure!(
+            code == 0,
+            "plugin compression failed with error code {code}"
+        );
+        anyhow::ensure!(
+            len <= vec.len(),
+            "plugin compression failed: compressed size exceeds the bound"
+        );
+        vec.truncate(len);
+        Ok(vec)
+    }
+}
+
+impl Decompressor for PluginScheme {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let codec = unsafe { &*self.codec };
+        let code = unsafe {
+            (codec.decompress)(
+                codec.context,
+                src.as_ptr(),
+                src.len(),
+                dst.as_mut_ptr(),
+                dst.len(),
+            )
+        };
+        anyhow::ensure!(
+            code == 0,
+            "plugin decompression failed with error code {code}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Sampling a tree of user files into a single corpus, for `ccmp probe`, which recommends schemes
+//! for the user's data instead of the standard corpora.
+
+use anyhow::Context as _;
+use std::io::{Read as _, Seek as _};
+
+/// At most this much data is sampled, larger trees are sampled down to it.
+pub const MAX_SIZE: u64 = 32 << 20;
+/// At most this much of each file is sampled, so that a few large files can't crowd out the rest.
+pub const MAX_FILE_SIZE: u64 = 4 << 20;
+/// Larger files are sampled in windows of this size, spread evenly over the file.
+pub const WINDOW_SIZE: u64 = 1 << 20;
+/// The sweep over every scheme only compresses this much of the sample, in windows of
+/// [`WINDOW_SIZE`] spread evenly over it, the candidates it finds are benchmarked on all of it.
+pub const SWEEP_SIZE: usize = 4 << 20;
+
+pub struct Sample {
+    /// The sampled parts of the files concatenated in path order, like in `read_corpus_data`.
+    pub data: std::vec::Vec<u8>,
+    pub files: usize,
+    pub sampled_files: usize,
+    /// The total size of all files.
+    pub size: u64,
+}
+
+/// Regular files below `path`, symbolic links aren't followed.
+fn collect_files(
+    path: &std::path::Path,
+    files: &mut std::vec::Vec<(std::path::PathBuf, u64)>,
+) -> anyhow::Result<()> {
+    let metadata = std::fs::symlink_metadata(path)
+        .with_context(|| format!("couldn't read metadata of {}", path.display()))?;
+    if metadata.is_dir() {
+        for entry in
+            std::fs::read_dir(path).with_context(|| format!("couldn't list {}", path.display()))?
+        {
+            collect_files(&entry?.path(), files)?;
+        }
+    } else if metadata.is_file() {
+        files.push((path.to_path_buf(), metadata.len()));
+    }
+    Ok(())
+}
+
+/// Append `take` bytes of the file at `path` of `len` bytes to `data`: all of it, or windows
+/// spread evenly from its start to its end.
+fn read_sampled(
+    path: &std::path::Path,
+    len: u64,
+    take: u64,
+    data: &mut std::vec::Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("couldn't open {}", path.display()))?;
+    let windows = if take == len {
+        1
+    } else {
+        take.div_ceil(WINDOW_SIZE)
+    };
+    for i in 0..windows {
+        let window = if windows == 1 {
+            take
+        } else {
+            (take - i * WINDOW_SIZE).min(WINDOW_SIZE)
+        };
+        if windows > 1 {
+            file.seek(std::io::SeekFrom::Start((len - window) * i / (windows - 1)))?;
+        }
+        // the file can shrink while it's read, which only makes the sample smaller
+        file.by_ref()
+            .take(window)
+            .read_to_end(data)
+            .with_context(|| format!("couldn't read from file {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Sample the file at `path`, or the files in the tree at `path`. If there is more than
+/// [`MAX_SIZE`] to sample, a random subset of the files is sampled, which is the same on every
+/// call for the same tree.
+pub fn sample<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Sample> {
+    let mut files = vec![];
+    collect_files(path.as_ref(), &mut files)?;
+    files.sort();
+    let size = files.iter().map(|(_, len)| len).sum();
+
+    let mut order: std::vec::Vec<usize> = (0..files.len()).collect();
+    let capped_size: u64 = files.iter().map(|(_, len)| *len.min(&MAX_FILE_SIZE)).sum();
+    if capped_size > MAX_SIZE {
+        let mut rng = crate::synthetic::Rng::new(1);
+        for i in (1..order.len()).rev() {
+            order.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+    }
+    let mut budget = MAX_SIZE;
+    let mut chosen = vec![];
+    for i in order {
+        let take = files[i].1.min(MAX_FILE_SIZE).min(budget);
+        if take > 0 {
+            chosen.push((i, take));
+            budget -= take;
+        }
+    }
+    // similar files tend to be next to each other, which compressors with large windows exploit
+    chosen.sort();
+
+    let mut data = vec![];
+    for &(i, take) in chosen.iter() {
+        let (path, len) = &files[i];
+        read_sampled(path, *len, take, &mut data)?;
+    }
+    anyhow::ensure!(!data.is_empty(), "nothing to sample");
+    Ok(Sample {
+        data,
+        files: files.len(),
+        sampled_files: chosen.len(),
+        size,
+    })
+}
+
+/// The part of the sample `data` that the sweep compresses, see [`SWEEP_SIZE`].
+pub fn sweep_sample(data: &[u8]) -> std::vec::Vec<u8> {
+    if data.len() <= SWEEP_SIZE {
+        return data.to_vec();
+    }
+    let window = WINDOW_SIZE as usize;
+    let windows = SWEEP_SIZE / window;
+    (0..windows)
+        .flat_map(|i| {
+            let start = (data.len() - window) * i / (windows - 1);
+            &data[start..start + window]
+        })
+        .copied()
+        .collect()
+}
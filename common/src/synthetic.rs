@@ -0,0 +1,225 @@
+//! Deterministic, seeded generators for synthetic corpora.
+//!
+//! These cover edge cases that the natural corpora don't, e.g. incompressible data or long runs of zeros.
+//! All generators produce exactly `len` bytes and the same output for the same seed.
+
+use crate::Corpus;
+
+/// Size of each synthetic corpus in `read_corpora`.
+pub const SIZE: usize = 1 << 22;
+
+const SEED: u64 = 0x5eed;
+
+/// SplitMix64, which is small, fast and good enough for generating test data.
+/// Implemented here so that the corpora don't change with the version of some dependency.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in `0..n` (up to negligible bias).
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+const WORDS: [&str; 32] = [
+    "the", "of", "and", "to", "in", "a", "is", "that", "for", "it", "as", "was", "with", "be",
+    "by", "on", "not", "he", "this", "are", "or", "his", "from", "at", "which", "but", "have",
+    "an", "had", "they", "you", "were",
+];
+
+fn text(len: usize, rng: &mut Rng) -> std::vec::Vec<u8> {
+    let mut result = std::vec::Vec::with_capacity(len + 16);
+    while result.len() < len {
+        result.extend_from_slice(rng.pick(&WORDS).as_bytes());
+        result.push(if rng.below(12) == 0 { b'\n' } else { b' ' });
+    }
+    result.truncate(len);
+    result
+}
+
+fn lines<F: FnMut(&mut std::vec::Vec<u8>, &mut Rng)>(
+    len: usize,
+    seed: u64,
+    mut line: F,
+) -> std::vec::Vec<u8> {
+    let mut rng = Rng::new(seed);
+    let mut result = std::vec::Vec::with_capacity(len + 256);
+    while result.len() < len {
+        line(&mut result, &mut rng);
+    }
+    result.truncate(len);
+    result
+}
+
+pub fn zeros(len: usize) -> std::vec::Vec<u8> {
+    vec![0u8; len]
+}
+
+/// Incompressible data.
+pub fn uniform_random(len: usize, seed: u64) -> std::vec::Vec<u8> {
+    let mut rng = Rng::new(seed);
+    let mut result = std::vec::Vec::with_capacity(len + 8);
+    while result.len() < len {
+        result.extend_from_slice(&rng.next_u64().to_le_bytes());
+    }
+    result.truncate(len);
+    result
+}
+
+/// Independent symbols drawn from the given distribution of `(symbol, weight)` pairs.
+pub fn low_entropy(len: usize, seed: u64, distribution: &[(u8, u32)]) -> std::vec::Vec<u8> {
+    let total: u64 = distribution.iter().map(|&(_, w)| u64::from(w)).sum();
+    assert!(total > 0, "distribution must have a positive weight");
+    let mut rng = Rng::new(seed);
+    (0..len)
+        .map(|_| {
+            let mut x = rng.below(total);
+            for &(symbol, weight) in distribution {
+                if x < u64::from(weight) {
+                    return symbol;
+                }
+                x -= u64::from(weight);
+            }
+            unreachable!()
+        })
+        .collect()
+}
+
+/// Random text that repeats verbatim every `distance` bytes.
+pub fn repeated_text(len: usize, seed: u64, distance: usize) -> std::vec::Vec<u8> {
+    assert!(distance > 0, "repeat distance must be positive");
+    let unit = text(distance.min(len), &mut Rng::new(seed));
+    unit.iter().copied().cycle().take(len).collect()
+}
+
+/// One JSON object per line, as written by a typical web service.
+pub fn json_logs(len: usize, seed: u64) -> std::vec::Vec<u8> {
+    use std::io::Write as _;
+    const LEVELS: [&str; 4] = ["DEBUG", "INFO", "INFO", "WARN"];
+    const SERVICES: [&str; 4] = ["api", "auth", "billing", "search"];
+    const MESSAGES: [&str; 4] = [
+        "request completed",
+        "cache miss",
+        "upstream timeout, retrying",
+        "user session refreshed",
+    ];
+    let mut time_ms: u64 = 1_683_000_000_000;
+    lines(len, seed, |out, rng| {
+        time_ms += rng.below(50);
+        writeln!(
+            out,
+            r#"{{"timestamp":{},"level":"{}","service":"{}","request_id":"{:016x}","latency_ms":{},"status":{},"message":"{}"}}"#,
+            time_ms,
+            rng.pick(&LEVELS),
+            rng.pick(&SERVICES),
+            rng.next_u64(),
+            rng.below(2000),
+            rng.pick(&[200, 200, 200, 204, 404, 500]),
+            rng.pick(&MESSAGES),
+        )
+        .unwrap();
+    })
+}
+
+/// Sensor readings that change slowly over time.
+pub fn csv_telemetry(len: usize, seed: u64) -> std::vec::Vec<u8> {
+    use std::io::Write as _;
+    let mut time_s: u64 = 1_683_000_000;
+    let mut readings = [21.5f64, 45.0, 1013.25];
+    let mut result = b"timestamp,sensor_id,temperature,humidity,pressure\n".to_vec();
+    result.extend(lines(len.saturating_sub(result.len()), seed, |out, rng| {
+        time_s += 1;
+        for reading in readings.iter_mut() {
+            *reading += (rng.below(201) as f64 - 100.0) / 1000.0;
+        }
+        writeln!(
+            out,
+            "{},{},{:.2},{:.1},{:.2}",
+            time_s,
+            rng.below(16),
+            readings[0],
+            readings[1],
+            readings[2]
+        )
+        .unwrap();
+    }));
+    result.truncate(len);
+    result
+}
+
+/// Ascending little-endian `u32`s with small random gaps.
+pub fn sorted_integers(len: usize, seed: u64) -> std::vec::Vec<u8> {
+    let mut rng = Rng::new(seed);
+    let mut value: u32 = 0;
+    let mut result = std::vec::Vec::with_capacity(len + 4);
+    while result.len() < len {
+        value = value.wrapping_add(rng.below(64) as u32);
+        result.extend_from_slice(&value.to_le_bytes());
+    }
+    result.truncate(len);
+    result
+}
+
+pub fn corpora() -> std::vec::Vec<Corpus> {
+    vec![
+        Corpus {
+            name: "zeros",
+            data: zeros(SIZE),
+        },
+        Corpus {
+            name: "uniform random",
+            data: uniform_random(SIZE, SEED),
+        },
+        Corpus {
+            name: "low entropy random",
+            data: low_entropy(
+                SIZE,
+                SEED,
+                &[
+                    (b'a', 64),
+                    (b'b', 32),
+                    (b'c', 16),
+                    (b'd', 8),
+                    (b'e', 4),
+                    (b'f', 4),
+                ],
+            ),
+        },
+        Corpus {
+            name: "repeated text (16 KiB)",
+            data: repeated_text(SIZE, SEED, 1 << 14),
+        },
+        Corpus {
+            name: "repeated text (1 MiB)",
+            data: repeated_text(SIZE, SEED, 1 << 20),
+        },
+        Corpus {
+            name: "json logs",
+            data: json_logs(SIZE, SEED),
+        },
+        Corpus {
+            name: "csv telemetry",
+            data: csv_telemetry(SIZE, SEED),
+        },
+        Corpus {
+            name: "sorted integers",
+            data: sorted_integers(SIZE, SEED),
+        },
+    ]
+}
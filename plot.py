@@ -23,16 +23,25 @@ compression_ratio_by_scheme["uncompressed"] = 0
 # for easier comparison and better visualization, we group the schemes by what format they implement
 scheme_groups = [
     ["uncompressed"],
-    ["flate2 (rust)", "flate2 (zlib-ng)", "yazi"],
+    ["rle", "huffman", "range coder"],
+    [
+        "flate2 (rust)",
+        "flate2 (zlib-ng)",
+        "flate2 (zlib-rs)",
+        "libdeflate",
+        "miniz_oxide",
+        "yazi",
+        "gzip (cli)",
+    ],
     ["deflate", "zopfli", "zopfli-rs"],
-    ["lzzzz", "lz4_flex"],
-    ["rust-lzma", "lzma-rs"],
+    ["lzzzz", "lz4_flex", "lz4_flex (plugin)", "lz4 (cli)"],
+    ["rust-lzma", "xz2", "lzma-rs", "xz (cli)"],
     ["tetsy_snappy", "xsnappy", "snap"],
-    ["brotlic", "brotli"],
-    ["zstd"],
+    ["brotlic", "brotli", "brotli (cli)"],
+    ["zstd", "ruzstd", "zstd (cli)"],
     ["lzo1x-1"],
     ["lzss (dyn)", "lzss (static)"],
-    ["bzip2"],
+    ["bzip2", "bzip2 (libbz2-rs)", "bzip2 (cli)"],
 ]
 
 # sort members of each group by highest compression ratio and groups by highest compression ratio within the group
@@ -63,25 +72,65 @@ for row in all_results:
 
 # corpora = ["canterbury", "canterbury large", "silesia"]
 corpora = list(reversed(["canterbury", "canterbury large", "silesia"]))
+# the synthetic corpora of common/src/synthetic.rs, if the results include them
+corpora += [
+    corpus
+    for corpus in [
+        "zeros",
+        "uniform random",
+        "low entropy random",
+        "repeated text (16 KiB)",
+        "repeated text (1 MiB)",
+        "json logs",
+        "csv telemetry",
+        "sorted integers",
+    ]
+    if any(row["corpus"] == corpus for row in all_results)
+]
 
 # base colors from colorbrewer: https://colorbrewer2.org/#type=qualitative&scheme=Paired&n=10
 colors = {
     "uncompressed": "#000000",
-    **{x: "#1f78b4" for x in ["flate2 (rust)", "flate2 (zlib-ng)", "yazi"]},
+    **{x: "#999999" for x in ["rle", "huffman", "range coder"]},
+    **{
+        x: "#1f78b4"
+        for x in [
+            "flate2 (rust)",
+            "flate2 (zlib-ng)",
+            "flate2 (zlib-rs)",
+            "libdeflate",
+            "miniz_oxide",
+            "yazi",
+            "gzip (cli)",
+        ]
+    },
     **{x: "#33a02c" for x in ["deflate", "zopfli", "zopfli-rs"]},
-    **{x: "#e31a1c" for x in ["lzzzz", "lz4_flex"]},
-    **{x: "#a6cee3" for x in ["rust-lzma", "lzma-rs"]},
+    **{
+        x: "#e31a1c"
+        for x in ["lzzzz", "lz4_flex", "lz4_flex (plugin)", "lz4 (cli)"]
+    },
+    **{x: "#a6cee3" for x in ["rust-lzma", "xz2", "lzma-rs", "xz (cli)"]},
     **{x: "#b2df8a" for x in ["tetsy_snappy", "xsnappy", "snap"]},
-    **{x: "#fdbf6f" for x in ["brotlic", "brotli"]},
-    **{x: "#fb9a99" for x in ["zstd"]},
+    **{x: "#fdbf6f" for x in ["brotlic", "brotli", "brotli (cli)"]},
+    **{x: "#fb9a99" for x in ["zstd", "ruzstd", "zstd (cli)"]},
     **{x: "#cab2d6" for x in ["lzo1x-1"]},
     **{x: "#ff7f00" for x in ["lzss (dyn)", "lzss (static)"]},
-    **{x: "#6a3d9a" for x in ["bzip2"]},
+    **{x: "#6a3d9a" for x in ["bzip2", "bzip2 (libbz2-rs)", "bzip2 (cli)"]},
 }
 """
 colors = {
     "uncompressed": "#000000",
-    **{x: "#1f78b4" for x in ["flate2 (rust)", "flate2 (zlib-ng)", "yazi"]},
+    **{
+        x: "#1f78b4"
+        for x in [
+            "flate2 (rust)",
+            "flate2 (zlib-ng)",
+            "flate2 (zlib-rs)",
+            "libdeflate",
+            "miniz_oxide",
+            "yazi",
+        ]
+    },
     **{x: "#33a02c" for x in ["deflate", "zopfli", "zopfli-rs"]},
     "lzzzz": "#e31a1c",
     "lz4_flex": "#b00808",
@@ -99,15 +148,23 @@ colors = {
 """
 markers = {
     "uncompressed": "o",
+    "rle": "o",
+    "huffman": "s",
+    "range coder": "d",
     "flate2 (rust)": "s",
     "flate2 (zlib-ng)": "d",
+    "flate2 (zlib-rs)": "^",
+    "libdeflate": "v",
+    "miniz_oxide": "P",
     "yazi": "o",
     "deflate": "o",
     "zopfli": "d",
     "zopfli-rs": "s",
     "lzzzz": "d",
     "lz4_flex": "o",
+    "lz4_flex (plugin)": "P",
     "rust-lzma": "o",
+    "xz2": "d",
     "lzma-rs": "s",
     "tetsy_snappy": "o",
     "xsnappy": "s",
@@ -115,10 +172,24 @@ markers = {
     "brotlic": "o",
     "brotli": "s",
     "zstd": "o",
+    "ruzstd": "s",
     "lzo1x-1": "o",
     "lzss (dyn)": "o",
     "lzss (static)": "s",
     "bzip2": "o",
+    "bzip2 (libbz2-rs)": "s",
+    # the command-line tools, including process spawn cost
+    **{
+        x: "x"
+        for x in [
+            "gzip (cli)",
+            "lz4 (cli)",
+            "xz (cli)",
+            "brotli (cli)",
+            "zstd (cli)",
+            "bzip2 (cli)",
+        ]
+    },
 }
 
 
@@ -195,16 +266,20 @@ def plot_throughput_by_scheme(corpus):
 
     for i, scheme in enumerate(schemes, 1):
         ax.errorbar(
-            [i - 0.1 for row in results if row["scheme"] == scheme],
+            [
+                i - 0.1
+                for row in results
+                if row["scheme"] == scheme and row["compression speed (MB/s)"]
+            ],
             [
                 float(row["compression speed (MB/s)"])
                 for row in results
-                if row["scheme"] == scheme
+                if row["scheme"] == scheme and row["compression speed (MB/s)"]
             ],
             yerr=[
                 float(row["compression speed standard deviation (MB/s)"])
                 for row in results
-                if row["scheme"] == scheme
+                if row["scheme"] == scheme and row["compression speed (MB/s)"]
             ],
             # label = scheme,
             color=colors[scheme],
@@ -224,7 +299,7 @@ def plot_throughput_by_scheme(corpus):
                 if row["scheme"] == scheme and row["decompression speed (MB/s)"]
             ],
             yerr=[
-                float(row["compression speed standard deviation (MB/s)"])
+                float(row["decompression speed standard deviation (MB/s)"])
                 for row in results
                 if row["scheme"] == scheme and row["decompression speed (MB/s)"]
             ],
@@ -371,22 +446,30 @@ def plot_decompression_speed_versus_compression_speed(corpus):
             [
                 float(row["compression speed (MB/s)"])
                 for row in results
-                if row["scheme"] == scheme and row["decompression speed (MB/s)"]
+                if row["scheme"] == scheme
+                and row["compression speed (MB/s)"]
+                and row["decompression speed (MB/s)"]
             ],
             [
                 float(row["decompression speed (MB/s)"])
                 for row in results
-                if row["scheme"] == scheme and row["decompression speed (MB/s)"]
+                if row["scheme"] == scheme
+                and row["compression speed (MB/s)"]
+                and row["decompression speed (MB/s)"]
             ],
             xerr=[
                 float(row["compression speed standard deviation (MB/s)"])
                 for row in results
-                if row["scheme"] == scheme and row["decompression speed (MB/s)"]
+                if row["scheme"] == scheme
+                and row["compression speed (MB/s)"]
+                and row["decompression speed (MB/s)"]
             ],
             yerr=[
                 float(row["decompression speed standard deviation (MB/s)"])
                 for row in results
-                if row["scheme"] == scheme and row["decompression speed (MB/s)"]
+                if row["scheme"] == scheme
+                and row["compression speed (MB/s)"]
+                and row["decompression speed (MB/s)"]
             ],
             label=scheme,
             color=colors[scheme],
@@ -434,9 +517,88 @@ def plot_decompression_speed_versus_compression_speed(corpus):
     plt.savefig(f"plots/cs-ds-{corpus}.png", dpi=300)
 
 
+def plot_zopfli_versus_flate2(corpus):
+    results = [row for row in all_results if row["corpus"] == corpus]
+
+    # size reduction and slowdown of zopfli compared to flate2 level 9 with the same container format
+    baseline = {
+        row["settings"].split(" / ")[0]: row
+        for row in results
+        if row["scheme"] == "flate2 (rust)"
+        and row["settings"].endswith(" / level 9")
+        and row["compression speed (MB/s)"]
+    }
+
+    plt.clf()
+    plt.close()
+    fig, ax = plt.subplots(figsize=figsize)
+
+    ax.set_xscale("log")
+
+    plt.grid(visible=True, which="major", axis="both", linestyle="-")
+    plt.grid(visible=True, which="minor", axis="both", linestyle=":")
+    ax.set_axisbelow(True)
+
+    for scheme in ["zopfli", "zopfli-rs"]:
+        rows = [
+            (row, baseline[row["settings"].split(" / ")[0]])
+            for row in results
+            if row["scheme"] == scheme
+            and row["settings"].split(" / ")[0] in baseline
+            and row["compression speed (MB/s)"]
+        ]
+        ax.scatter(
+            [
+                float(base["compression speed (MB/s)"])
+                / float(row["compression speed (MB/s)"])
+                for row, base in rows
+            ],
+            [
+                1
+                - float(base["compression ratio"]) / float(row["compression ratio"])
+                for row, base in rows
+            ],
+            label=scheme,
+            color=colors[scheme],
+            marker=markers[scheme],
+            s=40,
+        )
+        for row, base in rows:
+            ax.annotate(
+                row["settings"],
+                (
+                    float(base["compression speed (MB/s)"])
+                    / float(row["compression speed (MB/s)"]),
+                    1
+                    - float(base["compression ratio"])
+                    / float(row["compression ratio"]),
+                ),
+                fontsize="xx-small",
+            )
+
+    ax.legend(loc="lower right", fontsize="small", ncols=1)
+
+    ax.text(
+        0.025,
+        0.975,
+        f"corpus: {corpus}",
+        size="large",
+        horizontalalignment="left",
+        verticalalignment="top",
+        transform=ax.transAxes,
+    )
+
+    ax.set_xlabel("compression time relative to flate2 level 9 (lower is better)")
+    ax.set_ylabel("1 - compressed size / flate2 level 9 size\n(higher is better)")
+
+    plt.tight_layout()
+    plt.savefig(f"plots/zopfli-{corpus}.png", dpi=300)
+
+
 for corpus in corpora:
     plot_compression_by_schemes(corpus=corpus)
     plot_throughput_by_scheme(corpus=corpus)
     plot_throughput_by_compression(corpus=corpus, which="compression")
     plot_throughput_by_compression(corpus=corpus, which="decompression")
     plot_decompression_speed_versus_compression_speed(corpus=corpus)
+    plot_zopfli_versus_flate2(corpus=corpus)
@@ -0,0 +1,100 @@
+//! Sample plugin (see `common::plugin`) with the block format of lz4_flex.
+//! The settings match the lz4_flex scheme so that results can be joined row by row.
+
+use common::plugin::{PluginCodec, PluginDescription, PLUGIN_ABI_VERSION};
+use std::ffi::c_int;
+
+const OK: c_int = 0;
+const ERROR: c_int = 1;
+
+static CODECS: [PluginCodec; 1] = [PluginCodec {
+    context: 0,
+    describe,
+    compress_bound,
+    compress,
+    decompress,
+}];
+
+/// Like `std::slice::from_raw_parts`, but C callers may pass null for empty buffers.
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    }
+}
+
+unsafe fn slice_mut<'a>(ptr: *mut u8, len: usize) -> &'a mut [u8] {
+    if len == 0 {
+        &mut []
+    } else {
+        std::slice::from_raw_parts_mut(ptr, len)
+    }
+}
+
+/// Panics must not unwind into the caller, lz4_flex 0.10 panics instead of returning an error
+/// for some too short output buffers.
+fn catch_unwind<T>(f: impl FnOnce() -> T) -> std::thread::Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+}
+
+unsafe extern "C" fn describe(_context: usize) -> PluginDescription {
+    PluginDescription {
+        name: c"lz4_flex (plugin)".as_ptr(),
+        settings: c"safe / Block".as_ptr(),
+    }
+}
+
+unsafe extern "C" fn compress_bound(_context: usize, src_len: usize) -> usize {
+    lz4_flex::block::get_maximum_output_size(src_len)
+}
+
+unsafe extern "C" fn compress(
+    _context: usize,
+    src: *const u8,
+    src_len: usize,
+    dst: *mut u8,
+    dst_capacity: usize,
+    dst_len: *mut usize,
+) -> c_int {
+    let result = catch_unwind(|| {
+        lz4_flex::block::compress_into(slice(src, src_len), slice_mut(dst, dst_capacity))
+    });
+    match result {
+        Ok(Ok(len)) => {
+            *dst_len = len;
+            OK
+        }
+        _ => ERROR,
+    }
+}
+
+unsafe extern "C" fn decompress(
+    _context: usize,
+    src: *const u8,
+    src_len: usize,
+    dst: *mut u8,
+    dst_len: usize,
+) -> c_int {
+    let result = catch_unwind(|| {
+        lz4_flex::block::decompress_into(slice(src, src_len), slice_mut(dst, dst_len))
+    });
+    match result {
+        Ok(Ok(len)) if len == dst_len => OK,
+        _ => ERROR,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn compression_plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+/// # Safety
+///
+/// `count` has to be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn compression_plugin_codecs(count: *mut usize) -> *const PluginCodec {
+    *count = CODECS.len();
+    CODECS.as_ptr()
+}
@@ -0,0 +1,191 @@
+//! Order-0 canonical Huffman coding of bytes.
+//! The compressed data is the uncompressed length, the code lengths of all 256 symbols (4 bits
+//! each) and the codes, most significant bit first.
+
+use common::{Compressor, Decompressor, DescribeScheme};
+
+// so that the code lengths fit in 4 bits and the decoding table stays small
+const MAX_CODE_LEN: u8 = 15;
+
+pub struct Huffman;
+
+impl DescribeScheme for Huffman {
+    fn name(&self) -> String {
+        "huffman".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        None
+    }
+}
+
+fn code_lengths(data: &[u8]) -> [u8; 256] {
+    let mut frequencies = [0u64; 256];
+    for &byte in data {
+        frequencies[usize::from(byte)] += 1;
+    }
+    loop {
+        let lengths = unlimited_code_lengths(&frequencies);
+        if lengths.iter().all(|&len| len <= MAX_CODE_LEN) {
+            return lengths;
+        }
+        // flatten the distribution until the tree is shallow enough, this costs a little
+        // compression but is much simpler than package-merge
+        for frequency in frequencies.iter_mut().filter(|f| **f > 0) {
+            *frequency = (*frequency / 2).max(1);
+        }
+    }
+}
+
+fn unlimited_code_lengths(frequencies: &[u64; 256]) -> [u8; 256] {
+    use std::cmp::Reverse;
+
+    let mut lengths = [0u8; 256];
+    // nodes 0 to 255 are the symbols, the inner nodes are appended
+    let mut parents = vec![usize::MAX; 256];
+    let mut heap: std::collections::BinaryHeap<_> = frequencies
+        .iter()
+        .enumerate()
+        .filter(|(_, &frequency)| frequency > 0)
+        .map(|(symbol, &frequency)| Reverse((frequency, symbol)))
+        .collect();
+    if heap.len() == 1 {
+        let Reverse((_, symbol)) = heap.pop().unwrap();
+        lengths[symbol] = 1;
+        return lengths;
+    }
+    while heap.len() > 1 {
+        let Reverse((a_frequency, a)) = heap.pop().unwrap();
+        let Reverse((b_frequency, b)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[a] = node;
+        parents[b] = node;
+        heap.push(Reverse((a_frequency + b_frequency, node)));
+    }
+    for (symbol, len) in lengths.iter_mut().enumerate() {
+        let mut node = symbol;
+        let mut depth = 0u32;
+        while parents[node] != usize::MAX {
+            node = parents[node];
+            depth += 1;
+        }
+        *len = depth.try_into().unwrap_or(u8::MAX);
+    }
+    lengths
+}
+
+/// Assign the codes in order of code length and then symbol, as in DEFLATE.
+fn canonical_codes(lengths: &[u8; 256]) -> [u16; 256] {
+    let mut counts = [0u16; MAX_CODE_LEN as usize + 1];
+    for &len in lengths {
+        counts[usize::from(len)] += 1;
+    }
+    counts[0] = 0;
+    let mut next_code = [0u16; MAX_CODE_LEN as usize + 1];
+    let mut code = 0;
+    for len in 1..=usize::from(MAX_CODE_LEN) {
+        code = (code + counts[len - 1]) << 1;
+        next_code[len] = code;
+    }
+    let mut codes = [0u16; 256];
+    for (symbol, &len) in lengths.iter().enumerate() {
+        if len > 0 {
+            codes[symbol] = next_code[usize::from(len)];
+            next_code[usize::from(len)] += 1;
+        }
+    }
+    codes
+}
+
+impl Compressor for Huffman {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let lengths = code_lengths(data);
+        let codes = canonical_codes(&lengths);
+
+        let mut vec = std::vec::Vec::with_capacity(8 + 128 + data.len() / 2);
+        crate::write_len(&mut vec, data.len());
+        vec.extend(lengths.chunks(2).map(|pair| pair[0] | (pair[1] << 4)));
+
+        let mut bits = 0u64;
+        let mut bit_count = 0u32;
+        for &byte in data {
+            let len = lengths[usize::from(byte)];
+            bits = (bits << len) | u64::from(codes[usize::from(byte)]);
+            bit_count += u32::from(len);
+            while bit_count >= 8 {
+                bit_count -= 8;
+                vec.push((bits >> bit_count) as u8);
+            }
+        }
+        if bit_count > 0 {
+            vec.push((bits << (8 - bit_count)) as u8);
+        }
+        Ok(vec)
+    }
+}
+
+impl Decompressor for Huffman {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let src = crate::read_len(src, dst)?;
+        anyhow::ensure!(
+            src.len() >= 128,
+            "huffman decompression failed: no code lengths"
+        );
+        let (packed_lengths, src) = src.split_at(128);
+        let mut lengths = [0u8; 256];
+        for (pair, &packed) in lengths.chunks_mut(2).zip(packed_lengths) {
+            pair[0] = packed & 0xf;
+            pair[1] = packed >> 4;
+        }
+        // an oversubscribed set of code lengths can't come from the encoder
+        let kraft_sum: u32 = lengths
+            .iter()
+            .filter(|&&len| len > 0)
+            .map(|&len| 1 << (MAX_CODE_LEN - len))
+            .sum();
+        anyhow::ensure!(
+            kraft_sum <= 1 << MAX_CODE_LEN,
+            "huffman decompression failed: invalid code lengths"
+        );
+        let codes = canonical_codes(&lengths);
+
+        // look up the symbol and code length by the next MAX_CODE_LEN bits, an entry of 0 is an
+        // unused code
+        let mut table = vec![0u16; 1 << MAX_CODE_LEN];
+        for (symbol, (&len, &code)) in lengths.iter().zip(&codes).enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let shift = MAX_CODE_LEN - len;
+            let start = usize::from(code) << shift;
+            table[start..start + (1 << shift)].fill(((symbol as u16) << 4) | u16::from(len));
+        }
+
+        let mut bits = 0u64;
+        let mut bit_count = 0u32;
+        let mut pos = 0;
+        for out in dst.iter_mut() {
+            while bit_count <= 56 {
+                // past the end of the input, pad with zeros and check afterwards
+                bits = (bits << 8) | u64::from(src.get(pos).copied().unwrap_or(0));
+                bit_count += 8;
+                pos += 1;
+            }
+            let index = (bits >> (bit_count - u32::from(MAX_CODE_LEN))) as usize;
+            let entry = table[index & ((1 << MAX_CODE_LEN) - 1)];
+            anyhow::ensure!(entry != 0, "huffman decompression failed: invalid code");
+            *out = (entry >> 4) as u8;
+            bit_count -= u32::from(entry & 0xf);
+        }
+        let consumed_bits = pos * 8 - bit_count as usize;
+        anyhow::ensure!(
+            consumed_bits <= src.len() * 8,
+            "huffman decompression failed: unexpected end of input"
+        );
+        anyhow::ensure!(
+            consumed_bits.div_ceil(8) == src.len(),
+            "huffman decompression failed: trailing data"
+        );
+        Ok(())
+    }
+}
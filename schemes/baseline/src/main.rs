@@ -0,0 +1,41 @@
+//! Simple reference coders without dependencies, to tell how much of a codec's compression comes
+//! from entropy coding versus match finding.
+
+use anyhow::Context as _;
+use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+
+mod huffman;
+mod range_coder;
+mod rle;
+
+/// Prepend the length of the uncompressed data, since the entropy coders can't tell where the
+/// data ends otherwise.
+fn write_len(vec: &mut std::vec::Vec<u8>, len: usize) {
+    vec.extend_from_slice(&u64::try_from(len).unwrap().to_le_bytes());
+}
+
+/// Split off the length written by `write_len` and check it against the destination buffer.
+fn read_len<'a>(src: &'a [u8], dst: &[u8]) -> anyhow::Result<&'a [u8]> {
+    anyhow::ensure!(src.len() >= 8, "compressed data too short");
+    let (len, rest) = src.split_at(8);
+    let len = u64::from_le_bytes(len.try_into().unwrap());
+    anyhow::ensure!(
+        usize::try_from(len).ok() == Some(dst.len()),
+        "dst buffer length mismatch"
+    );
+    Ok(rest)
+}
+
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for rle::Rle {}
+impl Scheme for huffman::Huffman {}
+impl Scheme for range_coder::RangeCoder {}
+
+fn main() -> anyhow::Result<()> {
+    let schemes: [Box<dyn Scheme>; 3] = [
+        Box::new(rle::Rle),
+        Box::new(huffman::Huffman),
+        Box::new(range_coder::RangeCoder),
+    ];
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
+}
@@ -0,0 +1,165 @@
+//! Order-0 adaptive binary range coding of bytes, as in LZMA: each byte is coded as 8 binary
+//! decisions along a binary tree, so the 255 adaptive probabilities of the tree model the byte
+//! distribution.
+//! The compressed data is the uncompressed length followed by the output of the range coder.
+
+use common::{Compressor, Decompressor, DescribeScheme};
+
+const PROBABILITY_BITS: u32 = 11;
+const INITIAL_PROBABILITY: u16 = 1 << (PROBABILITY_BITS - 1);
+// how fast the probabilities adapt, higher is slower
+const ADAPTATION_SHIFT: u32 = 5;
+const TOP: u32 = 1 << 24;
+
+pub struct RangeCoder;
+
+impl DescribeScheme for RangeCoder {
+    fn name(&self) -> String {
+        "range coder".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        None
+    }
+}
+
+struct Encoder {
+    low: u64,
+    range: u32,
+    // the last byte that could still change through a carry, followed by `pending - 1` 0xff bytes
+    cache: u8,
+    pending: u64,
+    output: std::vec::Vec<u8>,
+}
+
+impl Encoder {
+    fn new(output: std::vec::Vec<u8>) -> Self {
+        Self {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            pending: 1,
+            output,
+        }
+    }
+
+    fn encode_bit(&mut self, probability: &mut u16, bit: bool) {
+        let bound = (self.range >> PROBABILITY_BITS) * u32::from(*probability);
+        if bit {
+            self.low += u64::from(bound);
+            self.range -= bound;
+            *probability -= *probability >> ADAPTATION_SHIFT;
+        } else {
+            self.range = bound;
+            *probability += ((1 << PROBABILITY_BITS) - *probability) >> ADAPTATION_SHIFT;
+        }
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn shift_low(&mut self) {
+        if self.low < 0xff00_0000 || self.low >= 1 << 32 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            for _ in 0..self.pending {
+                self.output.push(byte.wrapping_add(carry));
+                byte = 0xff;
+            }
+            self.pending = 0;
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.pending += 1;
+        self.low = (self.low & 0x00ff_ffff) << 8;
+    }
+
+    fn finish(mut self) -> std::vec::Vec<u8> {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+        self.output
+    }
+}
+
+struct Decoder<'a> {
+    code: u32,
+    range: u32,
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(input: &'a [u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            input.len() >= 5 && input[0] == 0,
+            "range decoder failed: invalid input"
+        );
+        Ok(Self {
+            code: u32::from_be_bytes(input[1..5].try_into().unwrap()),
+            range: u32::MAX,
+            input,
+            pos: 5,
+        })
+    }
+
+    fn decode_bit(&mut self, probability: &mut u16) -> bool {
+        let bound = (self.range >> PROBABILITY_BITS) * u32::from(*probability);
+        let bit = if self.code < bound {
+            self.range = bound;
+            *probability += ((1 << PROBABILITY_BITS) - *probability) >> ADAPTATION_SHIFT;
+            false
+        } else {
+            self.code -= bound;
+            self.range -= bound;
+            *probability -= *probability >> ADAPTATION_SHIFT;
+            true
+        };
+        while self.range < TOP {
+            self.range <<= 8;
+            // past the end of the input, pad with zeros and check afterwards
+            self.code =
+                (self.code << 8) | u32::from(self.input.get(self.pos).copied().unwrap_or(0));
+            self.pos += 1;
+        }
+        bit
+    }
+}
+
+impl Compressor for RangeCoder {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut vec = std::vec::Vec::with_capacity(8 + data.len() / 2);
+        crate::write_len(&mut vec, data.len());
+        let mut encoder = Encoder::new(vec);
+        let mut probabilities = [INITIAL_PROBABILITY; 256];
+        for &byte in data {
+            let mut node = 1;
+            for i in (0..8).rev() {
+                let bit = (byte >> i) & 1 == 1;
+                encoder.encode_bit(&mut probabilities[node], bit);
+                node = (node << 1) | usize::from(bit);
+            }
+        }
+        Ok(encoder.finish())
+    }
+}
+
+impl Decompressor for RangeCoder {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let src = crate::read_len(src, dst)?;
+        let mut decoder = Decoder::new(src)?;
+        let mut probabilities = [INITIAL_PROBABILITY; 256];
+        for out in dst.iter_mut() {
+            let mut node = 1;
+            for _ in 0..8 {
+                let bit = decoder.decode_bit(&mut probabilities[node]);
+                node = (node << 1) | usize::from(bit);
+            }
+            *out = node as u8;
+        }
+        anyhow::ensure!(
+            decoder.pos <= src.len(),
+            "range decoder failed: unexpected end of input"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Byte-level run-length encoding in the PackBits format: a header byte `n` is followed either by
+//! `n + 1` literal bytes (`n < 128`) or by one byte that is repeated `257 - n` times (`n > 128`).
+
+use common::{Compressor, Decompressor, DescribeScheme};
+
+const MAX_RUN: usize = 128;
+// a run of two is as long as two literals, but breaks up the surrounding literals
+const MIN_RUN: usize = 3;
+
+pub struct Rle;
+
+impl DescribeScheme for Rle {
+    fn name(&self) -> String {
+        "rle".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        None
+    }
+}
+
+fn run_length(data: &[u8]) -> usize {
+    let first = data[0];
+    data.iter()
+        .take(MAX_RUN)
+        .take_while(|&&byte| byte == first)
+        .count()
+}
+
+impl Compressor for Rle {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut vec = std::vec::Vec::with_capacity(data.len() + data.len() / MAX_RUN + 1);
+        let mut pos = 0;
+        while pos < data.len() {
+            let run = run_length(&data[pos..]);
+            if run >= MIN_RUN {
+                vec.push((257 - run) as u8);
+                vec.push(data[pos]);
+                pos += run;
+                continue;
+            }
+            let start = pos;
+            while pos < data.len() && pos - start < MAX_RUN && run_length(&data[pos..]) < MIN_RUN {
+                pos += 1;
+            }
+            vec.push((pos - start - 1) as u8);
+            vec.extend_from_slice(&data[start..pos]);
+        }
+        Ok(vec)
+    }
+}
+
+impl Decompressor for Rle {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let mut src = src.iter();
+        let mut pos = 0;
+        while let Some(&header) = src.next() {
+            let (len, literal) = match header {
+                0..=127 => (usize::from(header) + 1, true),
+                128 => anyhow::bail!("rle decompression failed: invalid header"),
+                129..=255 => (257 - usize::from(header), false),
+            };
+            let out = dst
+                .get_mut(pos..pos + len)
+                .ok_or_else(|| anyhow::Error::msg("rle decompression failed: dst too short"))?;
+            if literal {
+                let bytes = src.as_slice().get(..len).ok_or_else(|| {
+                    anyhow::Error::msg("rle decompression failed: unexpected end of input")
+                })?;
+                out.copy_from_slice(bytes);
+                src.nth(len - 1);
+            } else {
+                let &byte = src.next().ok_or_else(|| {
+                    anyhow::Error::msg("rle decompression failed: unexpected end of input")
+                })?;
+                out.fill(byte);
+            }
+            pos += len;
+        }
+        anyhow::ensure!(pos == dst.len(), "rle decompression failed: dst too long");
+        Ok(())
+    }
+}
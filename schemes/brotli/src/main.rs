@@ -1,29 +1,94 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{benchmark, thread_counts, Chunked, Compressor, Decompressor, DescribeScheme};
 use std::io::Write;
 
+const BUFFER_SIZE: usize = 4096;
+
+// keep the settings in sync with the brotlic scheme so that results can be joined row by row
+#[derive(Clone, Copy)]
 struct Brotli {
     quality: u32,
     window_size: u32,
+    large_window: bool,
+    block_size: Option<u32>,
+    mode: Mode,
     buffer_size: usize,
 }
 
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Generic,
+    Text,
+    Font,
+}
+
+impl From<Mode> for brotli::enc::backward_references::BrotliEncoderMode {
+    fn from(mode: Mode) -> Self {
+        use brotli::enc::backward_references::BrotliEncoderMode;
+        match mode {
+            Mode::Generic => BrotliEncoderMode::BROTLI_MODE_GENERIC,
+            Mode::Text => BrotliEncoderMode::BROTLI_MODE_TEXT,
+            Mode::Font => BrotliEncoderMode::BROTLI_MODE_FONT,
+        }
+    }
+}
+
 impl DescribeScheme for Brotli {
     fn name(&self) -> String {
         "brotli".to_string()
     }
     fn settings(&self) -> Option<String> {
-        Some(format!(
-            "quality {} / window size {} / buffer size {}",
-            self.quality, self.window_size, self.buffer_size
-        ))
+        // the original grid of the smallest and largest window and block sizes keeps its labels, so
+        // that older results still line up
+        let extreme = |size, worst, best| match size {
+            s if s == worst => Some("Worst"),
+            s if s == best => Some("Best"),
+            _ => None,
+        };
+        if let (Some(window), Some(block), false, Mode::Generic, BUFFER_SIZE) = (
+            extreme(self.window_size, 10, 24),
+            self.block_size.and_then(|b| extreme(b, 16, 24)),
+            self.large_window,
+            self.mode,
+            self.buffer_size,
+        ) {
+            return Some(format!(
+                "quality {} / {window} window size / {block} block size",
+                self.quality
+            ));
+        }
+        let mut settings = format!(
+            "quality {} / window size {} / block size {} / mode {:?}",
+            self.quality,
+            self.window_size,
+            self.block_size
+                .map_or("default".to_string(), |b| b.to_string()),
+            self.mode
+        );
+        if self.large_window {
+            settings += " / large window";
+        }
+        // brotlic has no buffer size, so only label the rows that deviate from the default
+        if self.buffer_size != BUFFER_SIZE {
+            settings += &format!(" / buffer size {}", self.buffer_size);
+        }
+        Some(settings)
     }
 }
 
 impl Compressor for Brotli {
     fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let params = brotli::enc::BrotliEncoderParams {
+            quality: self.quality.try_into().unwrap(),
+            lgwin: self.window_size.try_into().unwrap(),
+            // 0 lets the encoder choose the block size from the quality
+            lgblock: self.block_size.unwrap_or(0).try_into().unwrap(),
+            mode: self.mode.into(),
+            large_window: self.large_window,
+            ..Default::default()
+        };
         let mut compressor =
-            brotli::CompressorWriter::new(vec![], self.buffer_size, self.quality, self.window_size);
+            brotli::CompressorWriter::with_params(vec![], self.buffer_size, &params);
         compressor
             .write_all(data)
             .context("brotli compression failed")?;
@@ -31,30 +96,162 @@ impl Compressor for Brotli {
     }
 }
 
+fn decompress(src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+    let expected_len = dst.len();
+    let mut cursor = std::io::Cursor::new(dst);
+    brotli::BrotliDecompress(&mut std::io::Cursor::new(src), &mut cursor)
+        .context("brotli decompression failed")?;
+    let actual_len: usize = cursor.position().try_into().unwrap();
+    assert_eq!(actual_len, expected_len);
+    Ok(())
+}
+
 impl Decompressor for Brotli {
     fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
-        let expected_len = dst.len();
-        let mut cursor = std::io::Cursor::new(dst);
-        brotli::BrotliDecompress(&mut std::io::Cursor::new(src), &mut cursor)
-            .context("brotli decompression failed")?;
-        let actual_len: usize = cursor.position().try_into().unwrap();
-        assert_eq!(actual_len, expected_len);
-        Ok(())
+        decompress(src, dst)
     }
 }
 
+struct BrotliMulti {
+    quality: u32,
+    window_size: u32,
+    threads: usize,
+}
+
+// `compress_multi` needs to own its input
+struct OwnedInput(std::vec::Vec<u8>);
+
+impl brotli::enc::SliceWrapper<u8> for OwnedInput {
+    fn slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DescribeScheme for BrotliMulti {
+    fn name(&self) -> String {
+        "brotli".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(format!(
+            "quality {} / window size {} / threads {}",
+            self.quality, self.window_size, self.threads
+        ))
+    }
+    fn threads(&self) -> std::num::NonZeroUsize {
+        std::num::NonZeroUsize::new(self.threads).unwrap()
+    }
+}
+
+impl Compressor for BrotliMulti {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let params = brotli::enc::BrotliEncoderParams {
+            quality: self.quality.try_into().unwrap(),
+            lgwin: self.window_size.try_into().unwrap(),
+            ..Default::default()
+        };
+        let mut alloc_per_thread: std::vec::Vec<_> = (0..self.threads)
+            .map(|_| {
+                brotli::enc::threading::SendAlloc::new(
+                    brotli::enc::StandardAlloc::default(),
+                    brotli::enc::UnionHasher::Uninit,
+                )
+            })
+            .collect();
+        let mut vec =
+            vec![0u8; brotli::enc::BrotliEncoderMaxCompressedSizeMulti(data.len(), self.threads)];
+        let len = brotli::enc::compress_multi(
+            &params,
+            &mut brotli::enc::threading::Owned::new(OwnedInput(data.to_vec())),
+            &mut vec,
+            &mut alloc_per_thread,
+        )
+        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
+        .context("brotli compression failed")?;
+        vec.truncate(len);
+        Ok(vec)
+    }
+}
+
+impl Decompressor for BrotliMulti {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress(src, dst)
+    }
+}
+
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Brotli {}
+impl Scheme for BrotliMulti {}
+impl Scheme for Chunked<Brotli> {}
+
 fn main() -> anyhow::Result<()> {
-    let mut schemes = vec![];
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
+    let standard = |quality, window_size| Brotli {
+        quality,
+        window_size,
+        large_window: false,
+        block_size: None,
+        mode: Mode::Generic,
+        buffer_size: BUFFER_SIZE,
+    };
     for quality in 0..=11 {
-        for window_size in [20, 21, 22] {
-            for buffer_size in [4096] {
-                schemes.push(Brotli {
-                    quality,
-                    window_size,
-                    buffer_size,
-                });
+        for window_size in 10..=24 {
+            // the full window range only for some qualities to keep the runtime manageable
+            if (20..=22).contains(&window_size) || [1, 5, 9, 11].contains(&quality) {
+                schemes.push(Box::new(standard(quality, window_size)));
+            }
+        }
+        for window_size in [10, 24] {
+            for block_size in [16, 24] {
+                schemes.push(Box::new(Brotli {
+                    block_size: Some(block_size),
+                    ..standard(quality, window_size)
+                }));
             }
         }
     }
-    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+    for quality in [5, 9, 11] {
+        for window_size in [26, 28, 30] {
+            schemes.push(Box::new(Brotli {
+                large_window: true,
+                ..standard(quality, window_size)
+            }));
+        }
+        // 16 and 24 are in the grid above
+        for block_size in 17..=23 {
+            schemes.push(Box::new(Brotli {
+                block_size: Some(block_size),
+                ..standard(quality, 24)
+            }));
+        }
+        for mode in [Mode::Text, Mode::Font] {
+            schemes.push(Box::new(Brotli {
+                mode,
+                ..standard(quality, 22)
+            }));
+        }
+    }
+    for quality in [5, 9] {
+        for buffer_size in [256, 1024, 16384, 65536, 1 << 20] {
+            schemes.push(Box::new(Brotli {
+                buffer_size,
+                ..standard(quality, 22)
+            }));
+        }
+    }
+    // native multithreading (compress_multi) vs. naive chunking with the same number of threads
+    for quality in [1, 5, 9, 11] {
+        for threads in thread_counts() {
+            schemes.push(Box::new(BrotliMulti {
+                quality,
+                window_size: 22,
+                threads: threads.get(),
+            }));
+            schemes.push(Box::new(Chunked {
+                inner: standard(quality, 22),
+                chunk_size: std::num::NonZeroUsize::new(1 << 22).unwrap(),
+                threads,
+            }));
+        }
+    }
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
 }
@@ -2,38 +2,28 @@ use anyhow::Context as _;
 use common::{benchmark, Compressor, Decompressor, DescribeScheme};
 use std::io::{Read, Write};
 
+// keep the settings in sync with the brotli scheme so that results can be joined row by row
 struct Brotli {
     quality: u8,
-    window: WindowSize,
-    block: BlockSize,
+    window_size: u8,
+    large_window: bool,
+    block_size: Option<u8>,
+    mode: Mode,
 }
 
 #[derive(Debug, Clone, Copy)]
-enum WindowSize {
-    Best,
-    Worst,
+enum Mode {
+    Generic,
+    Text,
+    Font,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum BlockSize {
-    Best,
-    Worst,
-}
-
-impl Into<brotlic::WindowSize> for WindowSize {
-    fn into(self) -> brotlic::WindowSize {
-        match self {
-            WindowSize::Best => brotlic::WindowSize::best(),
-            WindowSize::Worst => brotlic::WindowSize::worst(),
-        }
-    }
-}
-
-impl Into<brotlic::BlockSize> for BlockSize {
-    fn into(self) -> brotlic::BlockSize {
-        match self {
-            BlockSize::Best => brotlic::BlockSize::best(),
-            BlockSize::Worst => brotlic::BlockSize::worst(),
+impl From<Mode> for brotlic::CompressionMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Generic => brotlic::CompressionMode::Generic,
+            Mode::Text => brotlic::CompressionMode::Text,
+            Mode::Font => brotlic::CompressionMode::Font,
         }
     }
 }
@@ -43,22 +33,55 @@ impl DescribeScheme for Brotli {
         "brotlic".to_string()
     }
     fn settings(&self) -> Option<String> {
-        Some(format!(
-            "quality {} / {:?} window size / {:?} block size",
-            self.quality, self.window, self.block
-        ))
+        // the original grid of the smallest and largest window and block sizes keeps its labels, so
+        // that older results still line up
+        let extreme = |size, worst, best| match size {
+            s if s == worst => Some("Worst"),
+            s if s == best => Some("Best"),
+            _ => None,
+        };
+        if let (Some(window), Some(block), false, Mode::Generic) = (
+            extreme(self.window_size, 10, 24),
+            self.block_size.and_then(|b| extreme(b, 16, 24)),
+            self.large_window,
+            self.mode,
+        ) {
+            return Some(format!(
+                "quality {} / {window} window size / {block} block size",
+                self.quality
+            ));
+        }
+        let mut settings = format!(
+            "quality {} / window size {} / block size {} / mode {:?}",
+            self.quality,
+            self.window_size,
+            self.block_size
+                .map_or("default".to_string(), |b| b.to_string()),
+            self.mode
+        );
+        if self.large_window {
+            settings += " / large window";
+        }
+        Some(settings)
     }
 }
 
 impl Compressor for Brotli {
     fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut options = brotlic::BrotliEncoderOptions::new();
+        options
+            .quality(brotlic::Quality::new(self.quality).unwrap())
+            .mode(self.mode.into());
+        if self.large_window {
+            options.large_window_size(brotlic::LargeWindowSize::new(self.window_size).unwrap());
+        } else {
+            options.window_size(brotlic::WindowSize::new(self.window_size).unwrap());
+        }
+        if let Some(block_size) = self.block_size {
+            options.block_size(brotlic::BlockSize::new(block_size).unwrap());
+        }
         let mut compressor = brotlic::CompressorWriter::with_encoder(
-            brotlic::BrotliEncoderOptions::new()
-                .quality(brotlic::Quality::new(self.quality).unwrap())
-                .window_size(self.window.into())
-                .block_size(self.block.into())
-                .build()
-                .context("couldn't create brotli encoder")?,
+            options.build().context("couldn't create brotli encoder")?,
             vec![],
         );
         compressor
@@ -70,7 +93,13 @@ impl Compressor for Brotli {
 
 impl Decompressor for Brotli {
     fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
-        let mut decompressor = brotlic::DecompressorReader::new(src);
+        let mut decompressor = brotlic::DecompressorReader::with_decoder(
+            brotlic::BrotliDecoderOptions::new()
+                .large_window_size(self.large_window)
+                .build()
+                .context("couldn't create brotli decoder")?,
+            src,
+        );
         decompressor
             .read_exact(dst)
             .context("brotli decompression error")?;
@@ -86,16 +115,49 @@ impl Decompressor for Brotli {
 
 fn main() -> anyhow::Result<()> {
     let mut schemes = vec![];
+    let standard = |quality, window_size| Brotli {
+        quality,
+        window_size,
+        large_window: false,
+        block_size: None,
+        mode: Mode::Generic,
+    };
     for quality in 0..=11 {
-        for window in [WindowSize::Worst, WindowSize::Best] {
-            for block in [BlockSize::Worst, BlockSize::Best] {
+        for window_size in 10..=24 {
+            // the full window range only for some qualities to keep the runtime manageable
+            if (20..=22).contains(&window_size) || [1, 5, 9, 11].contains(&quality) {
+                schemes.push(standard(quality, window_size));
+            }
+        }
+        for window_size in [10, 24] {
+            for block_size in [16, 24] {
                 schemes.push(Brotli {
-                    quality,
-                    window,
-                    block,
+                    block_size: Some(block_size),
+                    ..standard(quality, window_size)
                 });
             }
         }
     }
+    for quality in [5, 9, 11] {
+        for window_size in [26, 28, 30] {
+            schemes.push(Brotli {
+                large_window: true,
+                ..standard(quality, window_size)
+            });
+        }
+        // 16 and 24 are in the grid above
+        for block_size in 17..=23 {
+            schemes.push(Brotli {
+                block_size: Some(block_size),
+                ..standard(quality, 24)
+            });
+        }
+        for mode in [Mode::Text, Mode::Font] {
+            schemes.push(Brotli {
+                mode,
+                ..standard(quality, 22)
+            });
+        }
+    }
     benchmark(std::io::stdout(), schemes).context("benchmark failed")
 }
@@ -1,9 +1,19 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{benchmark, detect::Format, Compressor, Decompressor, DescribeScheme};
 use std::io::Read as _;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Decoder {
+    MultiRead,
+    Read,
+    BufRead,
+}
+
 struct Bzip2 {
     compression: bzip2::Compression,
+    // 1 to 250, how hard to try the fast sort before falling back to a slower one on repetitive input
+    work_factor: Option<u32>,
+    decoder: Decoder,
 }
 
 impl DescribeScheme for Bzip2 {
@@ -11,34 +21,82 @@ impl DescribeScheme for Bzip2 {
         "bzip2".to_string()
     }
     fn settings(&self) -> Option<String> {
-        Some(format!("level {}", self.compression.level()))
+        let mut settings = format!("level {}", self.compression.level());
+        if let Some(work_factor) = self.work_factor {
+            settings += &format!(" / work factor {work_factor}");
+        }
+        match self.decoder {
+            Decoder::MultiRead => {}
+            Decoder::Read => settings += " / read decoder",
+            Decoder::BufRead => settings += " / bufread decoder",
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Bzip2)
     }
 }
 
 impl Compressor for Bzip2 {
     fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
-        let mut encoder = bzip2::read::BzEncoder::new(data, self.compression);
-        let mut vec = vec![];
-        encoder
-            .read_to_end(&mut vec)
-            .context("bzip2 compression error")?;
-        Ok(vec)
+        let Some(work_factor) = self.work_factor else {
+            let mut encoder = bzip2::read::BzEncoder::new(data, self.compression);
+            let mut vec = vec![];
+            encoder
+                .read_to_end(&mut vec)
+                .context("bzip2 compression error")?;
+            return Ok(vec);
+        };
+        // the encoder adapters don't take a work factor
+        let mut compress = bzip2::Compress::new(self.compression, work_factor);
+        let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+        loop {
+            let consumed: usize = compress.total_in().try_into().unwrap();
+            let status = compress
+                .compress_vec(&data[consumed..], &mut vec, bzip2::Action::Finish)
+                .context("bzip2 compression error")?;
+            if status == bzip2::Status::StreamEnd {
+                return Ok(vec);
+            }
+            if vec.len() == vec.capacity() {
+                vec.reserve(vec.capacity().max(4096));
+            }
+        }
+    }
+}
+
+fn read_exact<R: std::io::Read>(mut decoder: R, dst: &mut [u8]) -> anyhow::Result<()> {
+    decoder
+        .read_exact(dst)
+        .context("bzip2 decompression error")?;
+    let mut tmp = [0u8];
+    match decoder.read_exact(&mut tmp) {
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+        _ => Err(anyhow::Error::msg(
+            "bzip2 decompression error: dst too short",
+        )),
     }
 }
 
 impl Decompressor for Bzip2 {
     fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
-        let mut decoder = bzip2::read::MultiBzDecoder::new(src);
-        decoder
-            .read_exact(dst)
-            .context("bzip2 decompression error")?;
-        let mut tmp = [0u8];
-        match decoder.read_exact(&mut tmp) {
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
-            _ => Err(anyhow::Error::msg(
-                "bzip2 decompression error: dst too short",
-            )),
+        match self.decoder {
+            Decoder::MultiRead => read_exact(bzip2::read::MultiBzDecoder::new(src), dst),
+            Decoder::Read => read_exact(bzip2::read::BzDecoder::new(src), dst),
+            Decoder::BufRead => read_exact(bzip2::bufread::BzDecoder::new(src), dst),
+        }
+    }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut decompressed = vec![];
+        match self.decoder {
+            Decoder::MultiRead => {
+                bzip2::read::MultiBzDecoder::new(src).read_to_end(&mut decompressed)
+            }
+            Decoder::Read => bzip2::read::BzDecoder::new(src).read_to_end(&mut decompressed),
+            Decoder::BufRead => bzip2::bufread::BzDecoder::new(src).read_to_end(&mut decompressed),
         }
+        .context("bzip2 decompression error")?;
+        Ok(decompressed)
     }
 }
 
@@ -47,7 +105,25 @@ fn main() -> anyhow::Result<()> {
     for level in 1..=9 {
         schemes.push(Bzip2 {
             compression: bzip2::Compression::new(level),
+            work_factor: None,
+            decoder: Decoder::MultiRead,
         });
     }
+    for level in [1, 5, 9] {
+        for work_factor in [1, 10, 30, 100, 250] {
+            schemes.push(Bzip2 {
+                compression: bzip2::Compression::new(level),
+                work_factor: Some(work_factor),
+                decoder: Decoder::MultiRead,
+            });
+        }
+        for decoder in [Decoder::Read, Decoder::BufRead] {
+            schemes.push(Bzip2 {
+                compression: bzip2::Compression::new(level),
+                work_factor: None,
+                decoder,
+            });
+        }
+    }
     benchmark(std::io::stdout(), schemes).context("benchmark failed")
 }
@@ -0,0 +1,65 @@
+use anyhow::Context as _;
+use common::{benchmark, thread_counts, Chunked, Compressor, Decompressor, DescribeScheme};
+
+#[derive(Debug, Clone, Copy)]
+enum Inner {
+    Lz4Flex,
+    Snap,
+}
+
+impl DescribeScheme for Inner {
+    fn name(&self) -> String {
+        match self {
+            Inner::Lz4Flex => "lz4_flex".to_string(),
+            Inner::Snap => "snap".to_string(),
+        }
+    }
+    fn settings(&self) -> Option<String> {
+        // same settings as the unchunked schemes so that results can be joined
+        match self {
+            Inner::Lz4Flex => Some("safe / Block".to_string()),
+            Inner::Snap => Some("Raw".to_string()),
+        }
+    }
+}
+
+impl Compressor for Inner {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        match self {
+            Inner::Lz4Flex => Ok(lz4_flex::block::compress(data)),
+            Inner::Snap => snap::raw::Encoder::new()
+                .compress_vec(data)
+                .context("snappy compression failed"),
+        }
+    }
+}
+
+impl Decompressor for Inner {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let len = match self {
+            Inner::Lz4Flex => lz4_flex::block::decompress_into(src, dst)
+                .context("lz4_flex decompression error")?,
+            Inner::Snap => snap::raw::Decoder::new()
+                .decompress(src, dst)
+                .context("snappy decompression failed")?,
+        };
+        anyhow::ensure!(len == dst.len(), "dst buffer length does not match");
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut schemes = vec![];
+    for inner in [Inner::Lz4Flex, Inner::Snap] {
+        for chunk_size in [1 << 16, 1 << 18, 1 << 20, 1 << 22, 1 << 24] {
+            for threads in thread_counts() {
+                schemes.push(Chunked {
+                    inner,
+                    chunk_size: std::num::NonZeroUsize::new(chunk_size).unwrap(),
+                    threads,
+                });
+            }
+        }
+    }
+    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+}
@@ -0,0 +1,90 @@
+use anyhow::Context as _;
+use common::{benchmark, detect::Format, ExternalCommand};
+
+/// The reference command-line tools at a few levels each.
+fn reference_tools() -> std::vec::Vec<ExternalCommand> {
+    let mut commands = vec![];
+    for level in ["-1", "-3", "-9", "-19"] {
+        commands.push(
+            ExternalCommand::new(
+                "zstd (cli)",
+                &["zstd", "-q", "-c", level],
+                &["zstd", "-q", "-d", "-c"],
+            )
+            .with_format(Format::Zstd),
+        );
+    }
+    for level in ["-1", "-6", "-9"] {
+        commands.push(
+            ExternalCommand::new("gzip (cli)", &["gzip", "-c", level], &["gzip", "-d", "-c"])
+                .with_format(Format::Gzip),
+        );
+        commands.push(
+            ExternalCommand::new("xz (cli)", &["xz", "-c", level], &["xz", "-d", "-c"])
+                .with_format(Format::Xz),
+        );
+    }
+    for level in ["-1", "-9"] {
+        commands.push(
+            ExternalCommand::new(
+                "lz4 (cli)",
+                &["lz4", "-q", "-c", level],
+                &["lz4", "-q", "-d", "-c"],
+            )
+            .with_format(Format::Lz4Frame),
+        );
+        commands.push(
+            ExternalCommand::new(
+                "bzip2 (cli)",
+                &["bzip2", "-c", level],
+                &["bzip2", "-d", "-c"],
+            )
+            .with_format(Format::Bzip2),
+        );
+    }
+    for quality in ["1", "5", "9", "11"] {
+        commands.push(ExternalCommand::new(
+            "brotli (cli)",
+            &["brotli", "-c", "-q", quality],
+            &["brotli", "-d", "-c"],
+        ));
+    }
+    commands
+}
+
+/// `--command <name> <compress command> <decompress command>`, with the commands split at
+/// whitespace, e.g. `--command mytool "mytool -c" "mytool -d"`.
+fn commands_from_args() -> anyhow::Result<std::vec::Vec<ExternalCommand>> {
+    let args: std::vec::Vec<String> = std::env::args().skip(1).collect();
+    let mut commands = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        anyhow::ensure!(arg == "--command", "unexpected argument {arg}");
+        let mut next = || {
+            args.next()
+                .context("--command needs a name, a compress and a decompress command")
+        };
+        let name = next()?;
+        let compress: std::vec::Vec<&str> = next()?.split_whitespace().collect();
+        let decompress: std::vec::Vec<&str> = next()?.split_whitespace().collect();
+        commands.push(ExternalCommand::new(name, &compress, &decompress));
+    }
+    Ok(commands)
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut commands = commands_from_args()?;
+    if commands.is_empty() {
+        commands = reference_tools()
+            .into_iter()
+            .filter(|command| {
+                let available = command.is_available();
+                if !available {
+                    eprintln!("skipping {}: not installed", command.compress.join(" "));
+                }
+                available
+            })
+            .collect();
+    }
+    benchmark(std::io::stdout(), commands).context("benchmark failed")
+}
@@ -2,6 +2,7 @@ use anyhow::Context as _;
 use common::{benchmark, Compressor, Decompressor, DescribeScheme};
 use std::io::{Read, Write};
 
+#[allow(clippy::enum_variant_names)]
 enum Deflate {
     Deflate(flate2::Compression),
     Zlib(flate2::Compression),
@@ -19,6 +20,13 @@ impl DescribeScheme for Deflate {
             Deflate::GZip(c) => Some(format!("gzip / level {}", c.level())),
         }
     }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self {
+            Deflate::Deflate(_) => None,
+            Deflate::Zlib(_) => Some(common::detect::Format::Zlib),
+            Deflate::GZip(_) => Some(common::detect::Format::Gzip),
+        }
+    }
 }
 
 impl Compressor for Deflate {
@@ -93,14 +101,111 @@ impl Decompressor for Deflate {
             }
         }
     }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut decompressed = vec![];
+        match self {
+            Deflate::Deflate(_) => {
+                flate2::read::DeflateDecoder::new(src).read_to_end(&mut decompressed)
+            }
+            Deflate::Zlib(_) => flate2::read::ZlibDecoder::new(src).read_to_end(&mut decompressed),
+            // files of unknown provenance can have several members, e.g. from pigz or `cat`
+            Deflate::GZip(_) => {
+                flate2::read::MultiGzDecoder::new(src).read_to_end(&mut decompressed)
+            }
+        }
+        .context("deflate decompression failed")?;
+        Ok(decompressed)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Deflate,
+    Zlib,
+}
+
+/// The low-level `Compress`/`Decompress` API, without the `Read`/`Write` adapters.
+struct Raw {
+    format: Format,
+    level: flate2::Compression,
+}
+
+impl DescribeScheme for Raw {
+    fn name(&self) -> String {
+        "flate2 (rust)".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(format!(
+            "{} / level {} / raw",
+            format!("{:?}", self.format).to_lowercase(),
+            self.level.level()
+        ))
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.format {
+            Format::Deflate => None,
+            Format::Zlib => Some(common::detect::Format::Zlib),
+        }
+    }
+}
+
+impl Compressor for Raw {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut compress = flate2::Compress::new(self.level, matches!(self.format, Format::Zlib));
+        let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+        loop {
+            let consumed: usize = compress.total_in().try_into().unwrap();
+            let status = compress
+                .compress_vec(&data[consumed..], &mut vec, flate2::FlushCompress::Finish)
+                .context("deflate compression failed")?;
+            if status == flate2::Status::StreamEnd {
+                return Ok(vec);
+            }
+            if vec.len() == vec.capacity() {
+                vec.reserve(vec.capacity().max(4096));
+            }
+        }
+    }
+}
+
+impl Decompressor for Raw {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let mut decompress = flate2::Decompress::new(matches!(self.format, Format::Zlib));
+        // decompress directly into dst, `decompress_vec` would need a copy
+        let status = decompress
+            .decompress(src, dst, flate2::FlushDecompress::Finish)
+            .context("deflate decompression failed")?;
+        anyhow::ensure!(
+            status == flate2::Status::StreamEnd,
+            "deflate decompression failed: dst too short"
+        );
+        let actual_len: usize = decompress.total_out().try_into().unwrap();
+        anyhow::ensure!(
+            actual_len == dst.len(),
+            "deflate decompression failed: dst too long"
+        );
+        Ok(())
+    }
 }
 
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Deflate {}
+impl Scheme for Raw {}
+
 fn main() -> anyhow::Result<()> {
-    let mut schemes = vec![];
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
+    for level in 0..=10 {
+        schemes.push(Box::new(Deflate::Deflate(flate2::Compression::new(level))));
+        schemes.push(Box::new(Deflate::Zlib(flate2::Compression::new(level))));
+        schemes.push(Box::new(Deflate::GZip(flate2::Compression::new(level))));
+    }
     for level in 0..=10 {
-        schemes.push(Deflate::Deflate(flate2::Compression::new(level)));
-        schemes.push(Deflate::Zlib(flate2::Compression::new(level)));
-        schemes.push(Deflate::GZip(flate2::Compression::new(level)));
+        for format in [Format::Deflate, Format::Zlib] {
+            schemes.push(Box::new(Raw {
+                format,
+                level: flate2::Compression::new(level),
+            }));
+        }
     }
-    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
 }
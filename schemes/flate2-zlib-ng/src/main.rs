@@ -2,6 +2,7 @@ use anyhow::Context as _;
 use common::{benchmark, Compressor, Decompressor, DescribeScheme};
 use std::io::{Read, Write};
 
+#[allow(clippy::enum_variant_names)]
 enum Deflate {
     Deflate(flate2::Compression),
     Zlib(flate2::Compression),
@@ -19,6 +20,13 @@ impl DescribeScheme for Deflate {
             Deflate::GZip(c) => Some(format!("gzip / level {}", c.level())),
         }
     }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self {
+            Deflate::Deflate(_) => None,
+            Deflate::Zlib(_) => Some(common::detect::Format::Zlib),
+            Deflate::GZip(_) => Some(common::detect::Format::Gzip),
+        }
+    }
 }
 
 impl Compressor for Deflate {
@@ -95,12 +103,288 @@ impl Decompressor for Deflate {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Deflate,
+    Zlib,
+    GZip,
+}
+
+/// The low-level `Compress`/`Decompress` API, without the `Read`/`Write` adapters.
+struct Raw {
+    format: Format,
+    level: flate2::Compression,
+    // 9 to 15, the default being 15
+    window_bits: Option<u8>,
+}
+
+impl DescribeScheme for Raw {
+    fn name(&self) -> String {
+        "flate2 (zlib-ng)".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!(
+            "{} / level {} / raw",
+            format!("{:?}", self.format).to_lowercase(),
+            self.level.level()
+        );
+        if let Some(bits) = self.window_bits {
+            settings += &format!(" / window bits {bits}");
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.format {
+            Format::Deflate => None,
+            Format::Zlib => Some(common::detect::Format::Zlib),
+            Format::GZip => Some(common::detect::Format::Gzip),
+        }
+    }
+}
+
+impl Compressor for Raw {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut compress = match (self.format, self.window_bits) {
+            (Format::Deflate, None) => flate2::Compress::new(self.level, false),
+            (Format::Zlib, None) => flate2::Compress::new(self.level, true),
+            (Format::Deflate, Some(bits)) => {
+                flate2::Compress::new_with_window_bits(self.level, false, bits)
+            }
+            (Format::Zlib, Some(bits)) => {
+                flate2::Compress::new_with_window_bits(self.level, true, bits)
+            }
+            (Format::GZip, bits) => flate2::Compress::new_gzip(self.level, bits.unwrap_or(15)),
+        };
+        let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+        loop {
+            let consumed: usize = compress.total_in().try_into().unwrap();
+            let status = compress
+                .compress_vec(&data[consumed..], &mut vec, flate2::FlushCompress::Finish)
+                .context("deflate compression failed")?;
+            if status == flate2::Status::StreamEnd {
+                return Ok(vec);
+            }
+            if vec.len() == vec.capacity() {
+                vec.reserve(vec.capacity().max(4096));
+            }
+        }
+    }
+}
+
+/// Decompress with the low-level `Decompress` API directly into `dst`.
+fn decompress_raw(
+    format: Format,
+    window_bits: Option<u8>,
+    src: &[u8],
+    dst: &mut [u8],
+) -> anyhow::Result<()> {
+    let mut decompress = match (format, window_bits) {
+        (Format::Deflate, None) => flate2::Decompress::new(false),
+        (Format::Zlib, None) => flate2::Decompress::new(true),
+        (Format::Deflate, Some(bits)) => flate2::Decompress::new_with_window_bits(false, bits),
+        (Format::Zlib, Some(bits)) => flate2::Decompress::new_with_window_bits(true, bits),
+        (Format::GZip, bits) => flate2::Decompress::new_gzip(bits.unwrap_or(15)),
+    };
+    // decompress directly into dst, `decompress_vec` would need a copy
+    let status = decompress
+        .decompress(src, dst, flate2::FlushDecompress::Finish)
+        .context("deflate decompression failed")?;
+    anyhow::ensure!(
+        status == flate2::Status::StreamEnd,
+        "deflate decompression failed: dst too short"
+    );
+    let actual_len: usize = decompress.total_out().try_into().unwrap();
+    anyhow::ensure!(
+        actual_len == dst.len(),
+        "deflate decompression failed: dst too long"
+    );
+    Ok(())
+}
+
+impl Decompressor for Raw {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress_raw(self.format, self.window_bits, src, dst)
+    }
+}
+
+/// The compression strategies of zlib-ng, which flate2 doesn't expose.
+#[derive(Debug, Clone, Copy)]
+enum Strategy {
+    Default,
+    Filtered,
+    HuffmanOnly,
+    Rle,
+    Fixed,
+}
+
+impl Strategy {
+    fn to_zlib(self) -> std::os::raw::c_int {
+        match self {
+            Strategy::Default => libz_ng_sys::Z_DEFAULT_STRATEGY,
+            Strategy::Filtered => libz_ng_sys::Z_FILTERED,
+            Strategy::HuffmanOnly => libz_ng_sys::Z_HUFFMAN_ONLY,
+            Strategy::Rle => libz_ng_sys::Z_RLE,
+            Strategy::Fixed => libz_ng_sys::Z_FIXED,
+        }
+    }
+}
+
+unsafe extern "C" fn zalloc(
+    _opaque: libz_ng_sys::voidpf,
+    items: libz_ng_sys::uInt,
+    size: libz_ng_sys::uInt,
+) -> libz_ng_sys::voidpf {
+    libc::calloc(items as usize, size as usize)
+}
+
+unsafe extern "C" fn zfree(_opaque: libz_ng_sys::voidpf, address: libz_ng_sys::voidpf) {
+    libc::free(address)
+}
+
+/// Compression strategies, with the deflate API of zlib-ng called directly through libz-ng-sys.
+/// Decompresses like [`Raw`], the strategy only matters to the encoder.
+struct Direct {
+    format: Format,
+    level: i32,
+    strategy: Strategy,
+}
+
+impl DescribeScheme for Direct {
+    fn name(&self) -> String {
+        "flate2 (zlib-ng)".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(format!(
+            "{} / level {} / strategy {}",
+            format!("{:?}", self.format).to_lowercase(),
+            self.level,
+            format!("{:?}", self.strategy).to_lowercase()
+        ))
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.format {
+            Format::Deflate => None,
+            Format::Zlib => Some(common::detect::Format::Zlib),
+            Format::GZip => Some(common::detect::Format::Gzip),
+        }
+    }
+}
+
+impl Compressor for Direct {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        // negative for a raw deflate stream, +16 for a gzip container
+        let window_bits = match self.format {
+            Format::Deflate => -15,
+            Format::Zlib => 15,
+            Format::GZip => 15 + 16,
+        };
+        let mut stream = libz_ng_sys::z_stream {
+            next_in: data.as_ptr().cast_mut(),
+            avail_in: data
+                .len()
+                .try_into()
+                .context("input too large for zlib-ng")?,
+            total_in: 0,
+            next_out: std::ptr::null_mut(),
+            avail_out: 0,
+            total_out: 0,
+            msg: std::ptr::null_mut(),
+            state: std::ptr::null_mut(),
+            zalloc,
+            zfree,
+            opaque: std::ptr::null_mut(),
+            data_type: 0,
+            adler: 0,
+            reserved: 0,
+        };
+        // SAFETY: zlib-ng only reads the input through `next_in`, the input and output buffers
+        // outlive the stream, the output buffer has room for `avail_out` bytes, and the stream
+        // is ended before returning
+        unsafe {
+            let ret = libz_ng_sys::deflateInit2_(
+                &mut stream,
+                self.level,
+                libz_ng_sys::Z_DEFLATED,
+                window_bits,
+                8,
+                self.strategy.to_zlib(),
+                libz_ng_sys::zlibVersion(),
+                std::mem::size_of::<libz_ng_sys::z_stream>() as std::os::raw::c_int,
+            );
+            anyhow::ensure!(
+                ret == libz_ng_sys::Z_OK,
+                "couldn't create zlib-ng encoder: error {ret}"
+            );
+            let bound: usize = libz_ng_sys::deflateBound(&mut stream, data.len() as _)
+                .try_into()
+                .unwrap();
+            let mut vec = std::vec::Vec::with_capacity(bound);
+            stream.next_out = vec.as_mut_ptr();
+            // a bound beyond uInt only leaves the compression unfinished, which fails below
+            stream.avail_out = bound.try_into().unwrap_or(libz_ng_sys::uInt::MAX);
+            let ret = libz_ng_sys::deflate(&mut stream, libz_ng_sys::Z_FINISH);
+            let len = stream.total_out;
+            libz_ng_sys::deflateEnd(&mut stream);
+            anyhow::ensure!(
+                ret == libz_ng_sys::Z_STREAM_END,
+                "deflate compression failed: zlib-ng error {ret}"
+            );
+            vec.set_len(len);
+            Ok(vec)
+        }
+    }
+}
+
+impl Decompressor for Direct {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress_raw(self.format, None, src, dst)
+    }
+}
+
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Deflate {}
+impl Scheme for Raw {}
+impl Scheme for Direct {}
+
 fn main() -> anyhow::Result<()> {
-    let mut schemes = vec![];
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
+    for level in 0..=9 {
+        schemes.push(Box::new(Deflate::Deflate(flate2::Compression::new(level))));
+        schemes.push(Box::new(Deflate::Zlib(flate2::Compression::new(level))));
+        schemes.push(Box::new(Deflate::GZip(flate2::Compression::new(level))));
+    }
     for level in 0..=9 {
-        schemes.push(Deflate::Deflate(flate2::Compression::new(level)));
-        schemes.push(Deflate::Zlib(flate2::Compression::new(level)));
-        schemes.push(Deflate::GZip(flate2::Compression::new(level)));
+        for format in [Format::Deflate, Format::Zlib, Format::GZip] {
+            schemes.push(Box::new(Raw {
+                format,
+                level: flate2::Compression::new(level),
+                window_bits: None,
+            }));
+        }
+    }
+    for level in [1, 6, 9] {
+        for window_bits in 9..=14 {
+            schemes.push(Box::new(Raw {
+                format: Format::Deflate,
+                level: flate2::Compression::new(level),
+                window_bits: Some(window_bits),
+            }));
+        }
+    }
+    for level in [1, 6, 9] {
+        for strategy in [
+            Strategy::Default,
+            Strategy::Filtered,
+            Strategy::HuffmanOnly,
+            Strategy::Rle,
+            Strategy::Fixed,
+        ] {
+            schemes.push(Box::new(Direct {
+                format: Format::Deflate,
+                level,
+                strategy,
+            }));
+        }
     }
-    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
 }
@@ -0,0 +1,230 @@
+use anyhow::Context as _;
+use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use std::io::{Read, Write};
+
+#[allow(clippy::enum_variant_names)]
+enum Deflate {
+    Deflate(flate2::Compression),
+    Zlib(flate2::Compression),
+    GZip(flate2::Compression),
+}
+
+impl DescribeScheme for Deflate {
+    fn name(&self) -> String {
+        "flate2 (zlib-rs)".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        match self {
+            Deflate::Deflate(c) => Some(format!("deflate / level {}", c.level())),
+            Deflate::Zlib(c) => Some(format!("zlib / level {}", c.level())),
+            Deflate::GZip(c) => Some(format!("gzip / level {}", c.level())),
+        }
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self {
+            Deflate::Deflate(_) => None,
+            Deflate::Zlib(_) => Some(common::detect::Format::Zlib),
+            Deflate::GZip(_) => Some(common::detect::Format::Gzip),
+        }
+    }
+}
+
+impl Compressor for Deflate {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        match self {
+            Deflate::Deflate(level) => {
+                let mut encoder = flate2::write::DeflateEncoder::new(vec![], *level);
+                encoder
+                    .write_all(data)
+                    .context("deflate compression failed")?;
+                encoder.finish().context("deflate compression failed")
+            }
+            Deflate::Zlib(level) => {
+                let mut encoder = flate2::write::ZlibEncoder::new(vec![], *level);
+                encoder
+                    .write_all(data)
+                    .context("deflate compression failed")?;
+                encoder.finish().context("zlib compression failed")
+            }
+            Deflate::GZip(level) => {
+                let mut encoder = flate2::write::GzEncoder::new(vec![], *level);
+                encoder
+                    .write_all(data)
+                    .context("deflate compression failed")?;
+                encoder.finish().context("gzip compression failed")
+            }
+        }
+    }
+}
+
+impl Decompressor for Deflate {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        match self {
+            Deflate::Deflate(_) => {
+                let mut decoder = flate2::read::DeflateDecoder::new(src);
+                decoder
+                    .read_exact(dst)
+                    .context("deflate decompression failed")?;
+                let mut tmp = [0u8];
+                match decoder.read_exact(&mut tmp) {
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+                    _ => Err(anyhow::Error::msg(
+                        "deflate decompression failed: dst too short",
+                    )),
+                }
+            }
+            Deflate::Zlib(_) => {
+                let mut decoder = flate2::read::ZlibDecoder::new(src);
+                decoder
+                    .read_exact(dst)
+                    .context("deflate decompression failed")?;
+                let mut tmp = [0u8];
+                match decoder.read_exact(&mut tmp) {
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+                    _ => Err(anyhow::Error::msg(
+                        "deflate decompression failed: dst too short",
+                    )),
+                }
+            }
+            Deflate::GZip(_) => {
+                let mut decoder = flate2::read::GzDecoder::new(src);
+                decoder
+                    .read_exact(dst)
+                    .context("deflate decompression failed")?;
+                let mut tmp = [0u8];
+                match decoder.read_exact(&mut tmp) {
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+                    _ => Err(anyhow::Error::msg(
+                        "deflate decompression failed: dst too short",
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Deflate,
+    Zlib,
+    GZip,
+}
+
+/// The low-level `Compress`/`Decompress` API, without the `Read`/`Write` adapters.
+struct Raw {
+    format: Format,
+    level: flate2::Compression,
+    // 9 to 15, the default being 15
+    window_bits: Option<u8>,
+}
+
+impl DescribeScheme for Raw {
+    fn name(&self) -> String {
+        "flate2 (zlib-rs)".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!(
+            "{} / level {} / raw",
+            format!("{:?}", self.format).to_lowercase(),
+            self.level.level()
+        );
+        if let Some(bits) = self.window_bits {
+            settings += &format!(" / window bits {bits}");
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.format {
+            Format::Deflate => None,
+            Format::Zlib => Some(common::detect::Format::Zlib),
+            Format::GZip => Some(common::detect::Format::Gzip),
+        }
+    }
+}
+
+impl Compressor for Raw {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut compress = match (self.format, self.window_bits) {
+            (Format::Deflate, None) => flate2::Compress::new(self.level, false),
+            (Format::Zlib, None) => flate2::Compress::new(self.level, true),
+            (Format::Deflate, Some(bits)) => {
+                flate2::Compress::new_with_window_bits(self.level, false, bits)
+            }
+            (Format::Zlib, Some(bits)) => {
+                flate2::Compress::new_with_window_bits(self.level, true, bits)
+            }
+            (Format::GZip, bits) => flate2::Compress::new_gzip(self.level, bits.unwrap_or(15)),
+        };
+        let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+        loop {
+            let consumed: usize = compress.total_in().try_into().unwrap();
+            let status = compress
+                .compress_vec(&data[consumed..], &mut vec, flate2::FlushCompress::Finish)
+                .context("deflate compression failed")?;
+            if status == flate2::Status::StreamEnd {
+                return Ok(vec);
+            }
+            if vec.len() == vec.capacity() {
+                vec.reserve(vec.capacity().max(4096));
+            }
+        }
+    }
+}
+
+impl Decompressor for Raw {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let mut decompress = match (self.format, self.window_bits) {
+            (Format::Deflate, None) => flate2::Decompress::new(false),
+            (Format::Zlib, None) => flate2::Decompress::new(true),
+            (Format::Deflate, Some(bits)) => flate2::Decompress::new_with_window_bits(false, bits),
+            (Format::Zlib, Some(bits)) => flate2::Decompress::new_with_window_bits(true, bits),
+            (Format::GZip, bits) => flate2::Decompress::new_gzip(bits.unwrap_or(15)),
+        };
+        // decompress directly into dst, `decompress_vec` would need a copy
+        let status = decompress
+            .decompress(src, dst, flate2::FlushDecompress::Finish)
+            .context("deflate decompression failed")?;
+        anyhow::ensure!(
+            status == flate2::Status::StreamEnd,
+            "deflate decompression failed: dst too short"
+        );
+        let actual_len: usize = decompress.total_out().try_into().unwrap();
+        anyhow::ensure!(
+            actual_len == dst.len(),
+            "deflate decompression failed: dst too long"
+        );
+        Ok(())
+    }
+}
+
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Deflate {}
+impl Scheme for Raw {}
+
+fn main() -> anyhow::Result<()> {
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
+    for level in 0..=9 {
+        schemes.push(Box::new(Deflate::Deflate(flate2::Compression::new(level))));
+        schemes.push(Box::new(Deflate::Zlib(flate2::Compression::new(level))));
+        schemes.push(Box::new(Deflate::GZip(flate2::Compression::new(level))));
+    }
+    for level in 0..=9 {
+        for format in [Format::Deflate, Format::Zlib, Format::GZip] {
+            schemes.push(Box::new(Raw {
+                format,
+                level: flate2::Compression::new(level),
+                window_bits: None,
+            }));
+        }
+    }
+    for level in [1, 6, 9] {
+        for window_bits in 9..=14 {
+            schemes.push(Box::new(Raw {
+                format: Format::Deflate,
+                level: flate2::Compression::new(level),
+                window_bits: Some(window_bits),
+            }));
+        }
+    }
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
+}
@@ -0,0 +1,191 @@
+use anyhow::Context as _;
+use common::{
+    benchmark, benchmark_decompression_only, detect::Format, Compressor, Decompressor,
+    DescribeScheme,
+};
+use std::io::Read as _;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Decoder {
+    MultiRead,
+    Read,
+    BufRead,
+}
+
+struct Bzip2 {
+    compression: bzip2::Compression,
+    // 1 to 250, how hard to try the fast sort before falling back to a slower one on repetitive input
+    work_factor: Option<u32>,
+    decoder: Decoder,
+}
+
+impl DescribeScheme for Bzip2 {
+    fn name(&self) -> String {
+        "bzip2 (libbz2-rs)".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!("level {}", self.compression.level());
+        if let Some(work_factor) = self.work_factor {
+            settings += &format!(" / work factor {work_factor}");
+        }
+        match self.decoder {
+            Decoder::MultiRead => {}
+            Decoder::Read => settings += " / read decoder",
+            Decoder::BufRead => settings += " / bufread decoder",
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Bzip2)
+    }
+}
+
+impl Compressor for Bzip2 {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let Some(work_factor) = self.work_factor else {
+            let mut encoder = bzip2::read::BzEncoder::new(data, self.compression);
+            let mut vec = vec![];
+            encoder
+                .read_to_end(&mut vec)
+                .context("bzip2 compression error")?;
+            return Ok(vec);
+        };
+        // the encoder adapters don't take a work factor
+        let mut compress = bzip2::Compress::new(self.compression, work_factor);
+        let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+        loop {
+            let consumed: usize = compress.total_in().try_into().unwrap();
+            let status = compress
+                .compress_vec(&data[consumed..], &mut vec, bzip2::Action::Finish)
+                .context("bzip2 compression error")?;
+            if status == bzip2::Status::StreamEnd {
+                return Ok(vec);
+            }
+            if vec.len() == vec.capacity() {
+                vec.reserve(vec.capacity().max(4096));
+            }
+        }
+    }
+}
+
+fn read_exact<R: std::io::Read>(mut decoder: R, dst: &mut [u8]) -> anyhow::Result<()> {
+    decoder
+        .read_exact(dst)
+        .context("bzip2 decompression error")?;
+    let mut tmp = [0u8];
+    match decoder.read_exact(&mut tmp) {
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+        _ => Err(anyhow::Error::msg(
+            "bzip2 decompression error: dst too short",
+        )),
+    }
+}
+
+impl Decompressor for Bzip2 {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        match self.decoder {
+            Decoder::MultiRead => read_exact(bzip2::read::MultiBzDecoder::new(src), dst),
+            Decoder::Read => read_exact(bzip2::read::BzDecoder::new(src), dst),
+            Decoder::BufRead => read_exact(bzip2::bufread::BzDecoder::new(src), dst),
+        }
+    }
+}
+
+/// Which side uses libbz2 instead of libbz2-rs.
+#[derive(Debug, Clone, Copy)]
+enum Libbz2 {
+    Encoder,
+    Decoder,
+}
+
+/// Decodes with one implementation what the other one encoded. Only decompression is timed, and
+/// the rows are named after the decoder: libbz2-rs for the output of libbz2, and the bzip2 scheme
+/// (libbz2) for the output of libbz2-rs.
+struct Cross {
+    level: u32,
+    libbz2: Libbz2,
+}
+
+impl DescribeScheme for Cross {
+    fn name(&self) -> String {
+        match self.libbz2 {
+            Libbz2::Encoder => "bzip2 (libbz2-rs)".to_string(),
+            Libbz2::Decoder => "bzip2".to_string(),
+        }
+    }
+    fn settings(&self) -> Option<String> {
+        Some(match self.libbz2 {
+            Libbz2::Encoder => format!("level {} / libbz2 encoder", self.level),
+            Libbz2::Decoder => format!("level {} / libbz2-rs encoder", self.level),
+        })
+    }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Bzip2)
+    }
+}
+
+impl Compressor for Cross {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut vec = vec![];
+        match self.libbz2 {
+            Libbz2::Encoder => {
+                libbz2::read::BzEncoder::new(data, libbz2::Compression::new(self.level))
+                    .read_to_end(&mut vec)
+            }
+            Libbz2::Decoder => {
+                bzip2::read::BzEncoder::new(data, bzip2::Compression::new(self.level))
+                    .read_to_end(&mut vec)
+            }
+        }
+        .context("bzip2 compression error")?;
+        Ok(vec)
+    }
+}
+
+impl Decompressor for Cross {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        match self.libbz2 {
+            Libbz2::Encoder => read_exact(bzip2::read::MultiBzDecoder::new(src), dst),
+            Libbz2::Decoder => read_exact(libbz2::read::MultiBzDecoder::new(src), dst),
+        }
+    }
+}
+
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Bzip2 {}
+
+fn main() -> anyhow::Result<()> {
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
+    for level in 1..=9 {
+        schemes.push(Box::new(Bzip2 {
+            compression: bzip2::Compression::new(level),
+            work_factor: None,
+            decoder: Decoder::MultiRead,
+        }));
+    }
+    for level in [1, 5, 9] {
+        for work_factor in [1, 10, 30, 100, 250] {
+            schemes.push(Box::new(Bzip2 {
+                compression: bzip2::Compression::new(level),
+                work_factor: Some(work_factor),
+                decoder: Decoder::MultiRead,
+            }));
+        }
+        for decoder in [Decoder::Read, Decoder::BufRead] {
+            schemes.push(Box::new(Bzip2 {
+                compression: bzip2::Compression::new(level),
+                work_factor: None,
+                decoder,
+            }));
+        }
+    }
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")?;
+
+    let mut cross = vec![];
+    for level in [1, 5, 9] {
+        for libbz2 in [Libbz2::Encoder, Libbz2::Decoder] {
+            cross.push(Cross { level, libbz2 });
+        }
+    }
+    benchmark_decompression_only(std::io::stdout(), cross).context("benchmark failed")
+}
@@ -0,0 +1,82 @@
+use anyhow::Context as _;
+use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Deflate,
+    Zlib,
+    GZip,
+}
+
+// the settings use the same format as the flate2 schemes so that results can be joined row by row
+struct Libdeflate {
+    format: Format,
+    level: i32,
+}
+
+impl DescribeScheme for Libdeflate {
+    fn name(&self) -> String {
+        "libdeflate".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(format!(
+            "{} / level {}",
+            format!("{:?}", self.format).to_lowercase(),
+            self.level
+        ))
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.format {
+            Format::Deflate => None,
+            Format::Zlib => Some(common::detect::Format::Zlib),
+            Format::GZip => Some(common::detect::Format::Gzip),
+        }
+    }
+}
+
+impl Compressor for Libdeflate {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let level = libdeflater::CompressionLvl::new(self.level)
+            .map_err(|_| anyhow::Error::msg("invalid libdeflate compression level"))?;
+        let mut compressor = libdeflater::Compressor::new(level);
+        // libdeflate only compresses whole buffers, so the output has to be big enough up front
+        let bound = match self.format {
+            Format::Deflate => compressor.deflate_compress_bound(data.len()),
+            Format::Zlib => compressor.zlib_compress_bound(data.len()),
+            Format::GZip => compressor.gzip_compress_bound(data.len()),
+        };
+        let mut vec = vec![0; bound];
+        let len = match self.format {
+            Format::Deflate => compressor.deflate_compress(data, &mut vec),
+            Format::Zlib => compressor.zlib_compress(data, &mut vec),
+            Format::GZip => compressor.gzip_compress(data, &mut vec),
+        }
+        .context("libdeflate compression failed")?;
+        vec.truncate(len);
+        Ok(vec)
+    }
+}
+
+impl Decompressor for Libdeflate {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let mut decompressor = libdeflater::Decompressor::new();
+        let len = match self.format {
+            Format::Deflate => decompressor.deflate_decompress(src, dst),
+            Format::Zlib => decompressor.zlib_decompress(src, dst),
+            Format::GZip => decompressor.gzip_decompress(src, dst),
+        }
+        .context("libdeflate decompression failed")?;
+        anyhow::ensure!(len == dst.len(), "dst buffer length mismatch");
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut schemes = vec![];
+    for level in 0..=12 {
+        for format in [Format::Deflate, Format::Zlib, Format::GZip] {
+            schemes.push(Libdeflate { format, level });
+        }
+    }
+    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+}
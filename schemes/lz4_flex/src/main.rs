@@ -1,11 +1,69 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{
+    benchmark, benchmark_concurrency, detect::Format, Compressor, Decompressor, DescribeScheme,
+};
 use std::io::{Read, Write};
 
-#[derive(Debug)]
+// keep the settings in sync with the lzzzz scheme so that results can be joined row by row
+#[derive(Debug, Clone, Copy)]
+struct FrameOptions {
+    block_size: usize,
+    independent_blocks: bool,
+    content_checksum: bool,
+    block_checksum: bool,
+    content_size: bool,
+}
+
+impl FrameOptions {
+    fn settings(&self) -> String {
+        let mut settings = format!(
+            "frame / block size {} / {} blocks",
+            self.block_size,
+            if self.independent_blocks {
+                "independent"
+            } else {
+                "linked"
+            }
+        );
+        if self.content_checksum {
+            settings += " / content checksum";
+        }
+        if self.block_checksum {
+            settings += " / block checksum";
+        }
+        if self.content_size {
+            settings += " / content size";
+        }
+        settings
+    }
+
+    fn all() -> std::vec::Vec<Self> {
+        let mut all = vec![];
+        for block_size in [1 << 16, 1 << 18, 1 << 20, 1 << 22] {
+            for independent_blocks in [false, true] {
+                for content_checksum in [false, true] {
+                    for block_checksum in [false, true] {
+                        for content_size in [false, true] {
+                            all.push(Self {
+                                block_size,
+                                independent_blocks,
+                                content_checksum,
+                                block_checksum,
+                                content_size,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        all
+    }
+}
+
 enum Lz4 {
     Block,
     Frame,
+    FrameOptions(FrameOptions),
 }
 
 impl DescribeScheme for Lz4 {
@@ -13,7 +71,17 @@ impl DescribeScheme for Lz4 {
         "lz4_flex".to_string()
     }
     fn settings(&self) -> Option<String> {
-        Some(format!("safe / {:?}", self))
+        Some(match self {
+            Lz4::Block => "safe / Block".to_string(),
+            Lz4::Frame => "safe / Frame".to_string(),
+            Lz4::FrameOptions(options) => options.settings(),
+        })
+    }
+    fn format(&self) -> Option<Format> {
+        match self {
+            Lz4::Block => None,
+            Lz4::Frame | Lz4::FrameOptions(_) => Some(Format::Lz4Frame),
+        }
     }
 }
 
@@ -28,6 +96,32 @@ impl Compressor for Lz4 {
                     .context("lz4_flex compression error")?;
                 compressor.finish().context("lz4_flex compression error")
             }
+            Lz4::FrameOptions(options) => {
+                let mut frame_info = lz4_flex::frame::FrameInfo::new();
+                frame_info.block_size = match options.block_size {
+                    0x1_0000 => lz4_flex::frame::BlockSize::Max64KB,
+                    0x4_0000 => lz4_flex::frame::BlockSize::Max256KB,
+                    0x10_0000 => lz4_flex::frame::BlockSize::Max1MB,
+                    0x40_0000 => lz4_flex::frame::BlockSize::Max4MB,
+                    size => anyhow::bail!("unsupported lz4 block size {size}"),
+                };
+                frame_info.block_mode = if options.independent_blocks {
+                    lz4_flex::frame::BlockMode::Independent
+                } else {
+                    lz4_flex::frame::BlockMode::Linked
+                };
+                frame_info.content_checksum = options.content_checksum;
+                frame_info.block_checksums = options.block_checksum;
+                if options.content_size {
+                    frame_info.content_size = Some(data.len().try_into().unwrap());
+                }
+                let mut compressor =
+                    lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, vec![]);
+                compressor
+                    .write_all(data)
+                    .context("lz4_flex compression error")?;
+                compressor.finish().context("lz4_flex compression error")
+            }
         }
     }
 }
@@ -41,7 +135,7 @@ impl Decompressor for Lz4 {
                 anyhow::ensure!(len == dst.len(), "dst buffer length does not match");
                 Ok(())
             }
-            Lz4::Frame => {
+            Lz4::Frame | Lz4::FrameOptions(_) => {
                 let mut decompressor = lz4_flex::frame::FrameDecoder::new(src);
                 decompressor
                     .read_exact(dst)
@@ -56,9 +150,27 @@ impl Decompressor for Lz4 {
             }
         }
     }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        match self {
+            Lz4::Block => anyhow::bail!("lz4 blocks don't record their decompressed length"),
+            Lz4::Frame | Lz4::FrameOptions(_) => {
+                let mut decompressed = vec![];
+                lz4_flex::frame::FrameDecoder::new(src)
+                    .read_to_end(&mut decompressed)
+                    .context("lz4_flex decompression error")?;
+                Ok(decompressed)
+            }
+        }
+    }
 }
 
 fn main() -> anyhow::Result<()> {
-    let schemes = [Lz4::Block, Lz4::Frame];
+    let mut schemes = vec![Lz4::Block, Lz4::Frame];
+    if std::env::args().any(|arg| arg == "--concurrency") {
+        return benchmark_concurrency(std::io::stdout(), schemes).context("benchmark failed");
+    }
+    for options in FrameOptions::all() {
+        schemes.push(Lz4::FrameOptions(options));
+    }
     benchmark(std::io::stdout(), schemes).context("benchmark failed")
 }
@@ -1,6 +1,12 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{benchmark, benchmark_decompression_only, Compressor, Decompressor, DescribeScheme};
+use std::io::Write as _;
 
+// input is fed to the streaming decoder in chunks of this size
+const STREAM_CHUNK_SIZE: usize = 1 << 16;
+
+// the variant names are the settings
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 enum Lzma {
     Lzma,
@@ -15,6 +21,14 @@ impl DescribeScheme for Lzma {
     fn settings(&self) -> Option<String> {
         Some(format!("{:?}", self))
     }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self {
+            Lzma::Lzma => Some(common::detect::Format::LzmaAlone),
+            // raw LZMA2 chunks without a container
+            Lzma::Lzma2 => None,
+            Lzma::Xz => Some(common::detect::Format::Xz),
+        }
+    }
 }
 
 impl Compressor for Lzma {
@@ -58,7 +72,230 @@ impl Decompressor for Lzma {
     }
 }
 
+fn check_len(writer: &std::io::Cursor<&mut [u8]>) -> anyhow::Result<()> {
+    let actual_len: usize = writer.position().try_into().unwrap();
+    anyhow::ensure!(
+        actual_len == writer.get_ref().len(),
+        "lzma decompression error: dst too short"
+    );
+    Ok(())
+}
+
+/// Decodes `.lzma` with the given options, either in one go or with the streaming decoder.
+fn decompress_lzma(
+    src: &[u8],
+    dst: &mut [u8],
+    options: &lzma_rs::decompress::Options,
+    stream: bool,
+) -> anyhow::Result<()> {
+    let mut writer = std::io::Cursor::new(dst);
+    if stream {
+        let mut decoder = lzma_rs::decompress::Stream::new_with_options(options, &mut writer);
+        for chunk in src.chunks(STREAM_CHUNK_SIZE) {
+            decoder
+                .write_all(chunk)
+                .context("lzma decompression failed")?;
+        }
+        decoder.finish().context("lzma decompression failed")?;
+    } else {
+        lzma_rs::lzma_decompress_with_options(&mut std::io::Cursor::new(src), &mut writer, options)
+            .context("lzma decompression failed")?;
+    }
+    check_len(&writer)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum UnpackedSize {
+    // unknown size in the header, the stream ends with an end-of-payload marker
+    EndMarker,
+    // size written to the header
+    Header,
+    // no size in the header, the decoder is told the size (non-standard)
+    Provided,
+}
+
+struct LzmaOptions {
+    unpacked_size: UnpackedSize,
+    memlimit: Option<usize>,
+    stream: bool,
+}
+
+impl DescribeScheme for LzmaOptions {
+    fn name(&self) -> String {
+        "lzma-rs".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!("Lzma / unpacked size {:?}", self.unpacked_size);
+        if let Some(memlimit) = self.memlimit {
+            settings += &format!(" / memlimit {memlimit}");
+        }
+        if self.stream {
+            settings += " / stream";
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.unpacked_size {
+            UnpackedSize::EndMarker | UnpackedSize::Header => {
+                Some(common::detect::Format::LzmaAlone)
+            }
+            UnpackedSize::Provided => None,
+        }
+    }
+}
+
+impl Compressor for LzmaOptions {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let unpacked_size = match self.unpacked_size {
+            UnpackedSize::EndMarker => lzma_rs::compress::UnpackedSize::WriteToHeader(None),
+            UnpackedSize::Header => {
+                lzma_rs::compress::UnpackedSize::WriteToHeader(Some(data.len().try_into().unwrap()))
+            }
+            UnpackedSize::Provided => lzma_rs::compress::UnpackedSize::SkipWritingToHeader,
+        };
+        let mut vec = vec![];
+        lzma_rs::lzma_compress_with_options(
+            &mut std::io::Cursor::new(data),
+            &mut vec,
+            &lzma_rs::compress::Options { unpacked_size },
+        )
+        .context("lzma compression failed")?;
+        Ok(vec)
+    }
+}
+
+impl Decompressor for LzmaOptions {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let unpacked_size = match self.unpacked_size {
+            UnpackedSize::EndMarker | UnpackedSize::Header => {
+                lzma_rs::decompress::UnpackedSize::ReadFromHeader
+            }
+            UnpackedSize::Provided => {
+                lzma_rs::decompress::UnpackedSize::UseProvided(Some(dst.len().try_into().unwrap()))
+            }
+        };
+        let options = lzma_rs::decompress::Options {
+            unpacked_size,
+            memlimit: self.memlimit,
+            allow_incomplete: false,
+        };
+        decompress_lzma(src, dst, &options, self.stream)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Lzma,
+    Xz,
+}
+
+/// lzma-rs decoding realistic streams from liblzma, since its own encoder barely compresses.
+/// rust-lzma can't write `.lzma`, so the input is produced with xz2 (the same liblzma encoder).
+struct Liblzma {
+    format: Format,
+    preset: u32,
+    stream: bool,
+}
+
+impl DescribeScheme for Liblzma {
+    fn name(&self) -> String {
+        "lzma-rs".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!("{:?} / liblzma preset {}", self.format, self.preset);
+        if self.stream {
+            settings += " / stream";
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        Some(match self.format {
+            Format::Lzma => common::detect::Format::LzmaAlone,
+            Format::Xz => common::detect::Format::Xz,
+        })
+    }
+}
+
+impl Compressor for Liblzma {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut stream = match self.format {
+            Format::Lzma => xz2::stream::LzmaOptions::new_preset(self.preset)
+                .and_then(|options| xz2::stream::Stream::new_lzma_encoder(&options)),
+            Format::Xz => {
+                xz2::stream::Stream::new_easy_encoder(self.preset, xz2::stream::Check::Crc64)
+            }
+        }
+        .context("couldn't create liblzma encoder")?;
+        let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+        loop {
+            let consumed: usize = stream.total_in().try_into().unwrap();
+            let status = stream
+                .process_vec(&data[consumed..], &mut vec, xz2::stream::Action::Finish)
+                .context("liblzma compression failed")?;
+            if status == xz2::stream::Status::StreamEnd {
+                return Ok(vec);
+            }
+            if vec.len() == vec.capacity() {
+                vec.reserve(vec.capacity().max(4096));
+            }
+        }
+    }
+}
+
+impl Decompressor for Liblzma {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        match self.format {
+            Format::Lzma => decompress_lzma(src, dst, &Default::default(), self.stream),
+            Format::Xz => {
+                let mut writer = std::io::Cursor::new(dst);
+                lzma_rs::xz_decompress(&mut std::io::Cursor::new(src), &mut writer)
+                    .context("xz decompression failed")?;
+                check_len(&writer)
+            }
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     let schemes = [Lzma::Lzma, Lzma::Lzma2, Lzma::Xz];
-    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+    benchmark(std::io::stdout(), schemes).context("benchmark failed")?;
+
+    let mut options = vec![];
+    for unpacked_size in [
+        UnpackedSize::EndMarker,
+        UnpackedSize::Header,
+        UnpackedSize::Provided,
+    ] {
+        for stream in [false, true] {
+            options.push(LzmaOptions {
+                unpacked_size,
+                memlimit: None,
+                stream,
+            });
+        }
+    }
+    // the dictionary size of the lzma-rs encoder, so the limit is never hit
+    options.push(LzmaOptions {
+        unpacked_size: UnpackedSize::EndMarker,
+        memlimit: Some(1 << 23),
+        stream: false,
+    });
+    benchmark(std::io::stdout(), options).context("benchmark failed")?;
+
+    let mut liblzma = vec![];
+    for preset in [1, 6, 9] {
+        liblzma.push(Liblzma {
+            format: Format::Xz,
+            preset,
+            stream: false,
+        });
+        for stream in [false, true] {
+            liblzma.push(Liblzma {
+                format: Format::Lzma,
+                preset,
+                stream,
+            });
+        }
+    }
+    benchmark_decompression_only(std::io::stdout(), liblzma).context("benchmark failed")
 }
@@ -3,7 +3,16 @@ use common::{benchmark, Compressor, Decompressor, DescribeScheme};
 
 struct LzssDyn(lzss::LzssDyn);
 
-struct LzssStatic<const EI: usize, const EJ: usize, const C: u8, const N: usize, const N2: usize>();
+struct LzssStatic<const EI: usize, const EJ: usize, const C: u8, const N: usize, const N2: usize>(
+    Buffer,
+);
+
+// where the generic implementation allocates its window (N2 bytes to compress, N to decompress)
+#[derive(Clone, Copy)]
+enum Buffer {
+    Stack,
+    Heap,
+}
 
 impl DescribeScheme for LzssDyn {
     fn name(&self) -> String {
@@ -11,7 +20,12 @@ impl DescribeScheme for LzssDyn {
     }
     fn settings(&self) -> Option<String> {
         let LzssDyn(compressor) = self;
-        Some(format!("ei={}/ej={}", compressor.ei(), compressor.ej()))
+        Some(format!(
+            "ei={}/ej={}/c={:#04x}",
+            compressor.ei(),
+            compressor.ej(),
+            compressor.c()
+        ))
     }
 }
 
@@ -22,7 +36,17 @@ impl<const EI: usize, const EJ: usize, const C: u8, const N: usize, const N2: us
         "lzss (static)".to_string()
     }
     fn settings(&self) -> Option<String> {
-        Some(format!("ei={}/ej={}", EI, EJ))
+        let Self(buffer) = self;
+        Some(format!(
+            "ei={}/ej={}/c={:#04x}{}",
+            EI,
+            EJ,
+            C,
+            match buffer {
+                Buffer::Stack => "",
+                Buffer::Heap => "/heap",
+            }
+        ))
     }
 }
 
@@ -42,11 +66,14 @@ impl<const EI: usize, const EJ: usize, const C: u8, const N: usize, const N2: us
     for LzssStatic<EI, EJ, C, N, N2>
 {
     fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
-        lzss::Lzss::<EI, EJ, C, N, N2>::compress_stack(
-            lzss::SliceReader::new(data),
-            lzss::VecWriter::with_capacity(0),
-        )
-        .context("LzssDyn compression error")
+        let Self(buffer) = self;
+        let reader = lzss::SliceReader::new(data);
+        let writer = lzss::VecWriter::with_capacity(0);
+        match buffer {
+            Buffer::Stack => lzss::Lzss::<EI, EJ, C, N, N2>::compress_stack(reader, writer),
+            Buffer::Heap => lzss::Lzss::<EI, EJ, C, N, N2>::compress_heap(reader, writer),
+        }
+        .context("LzssStatic compression error")
     }
 }
 
@@ -66,55 +93,64 @@ impl<const EI: usize, const EJ: usize, const C: u8, const N: usize, const N2: us
     for LzssStatic<EI, EJ, C, N, N2>
 {
     fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
-        lzss::Lzss::<EI, EJ, C, N, N2>::decompress_stack(
-            lzss::SliceReader::new(src),
-            lzss::SliceWriterExact::new(dst),
-        )
-        .context("LzssDyn compression error")
+        let Self(buffer) = self;
+        let reader = lzss::SliceReader::new(src);
+        let writer = lzss::SliceWriterExact::new(dst);
+        match buffer {
+            Buffer::Stack => lzss::Lzss::<EI, EJ, C, N, N2>::decompress_stack(reader, writer),
+            Buffer::Heap => lzss::Lzss::<EI, EJ, C, N, N2>::decompress_heap(reader, writer),
+        }
+        .context("LzssStatic decompression error")
     }
 }
 
-trait Foo: Compressor + Decompressor + DescribeScheme {}
+trait Foo: Compressor + Decompressor + DescribeScheme + Sync {}
 impl Foo for LzssDyn {}
 impl<const EI: usize, const EJ: usize, const C: u8, const N: usize, const N2: usize> Foo
     for LzssStatic<EI, EJ, C, N, N2>
 {
 }
 
+// the parameters of the generic implementation have to be known at compile time, so the grid is
+// spelled out as the cross product of the given offset bits (ei) and length bits (ej)
+macro_rules! push_static {
+    ($schemes:ident, $c:literal, [$($ei:literal),*], $ej:literal) => {
+        $(
+            for buffer in [Buffer::Stack, Buffer::Heap] {
+                $schemes.push(Box::new(
+                    LzssStatic::<$ei, $ej, $c, { 1 << $ei }, { 2 << $ei }>(buffer),
+                ));
+            }
+        )*
+    };
+    ($schemes:ident, $c:literal, $eis:tt, [$($ej:literal),*]) => {
+        $(push_static!($schemes, $c, $eis, $ej);)*
+    };
+}
+
 fn main() -> anyhow::Result<()> {
     let mut schemes: std::vec::Vec<Box<dyn Foo>> = vec![];
 
-    schemes.push(Box::new(
-        LzssStatic::<10, 4, 0x20, { 1 << 10 }, { 2 << 10 }>(),
-    ));
-    schemes.push(Box::new(
-        LzssStatic::<11, 4, 0x20, { 1 << 11 }, { 2 << 11 }>(),
-    ));
-    schemes.push(Box::new(
-        LzssStatic::<12, 4, 0x20, { 1 << 12 }, { 2 << 12 }>(),
-    ));
-    schemes.push(Box::new(
-        LzssStatic::<13, 4, 0x20, { 1 << 13 }, { 2 << 13 }>(),
-    ));
-    schemes.push(Box::new(
-        LzssStatic::<10, 5, 0x20, { 1 << 10 }, { 2 << 10 }>(),
-    ));
-    schemes.push(Box::new(
-        LzssStatic::<11, 5, 0x20, { 1 << 11 }, { 2 << 11 }>(),
-    ));
-    schemes.push(Box::new(
-        LzssStatic::<12, 5, 0x20, { 1 << 12 }, { 2 << 12 }>(),
-    ));
-    schemes.push(Box::new(
-        LzssStatic::<13, 5, 0x20, { 1 << 13 }, { 2 << 13 }>(),
-    ));
+    push_static!(
+        schemes,
+        0x00,
+        [8, 9, 10, 11, 12, 13, 14, 15, 16],
+        [3, 4, 5, 6]
+    );
+    push_static!(
+        schemes,
+        0x20,
+        [8, 9, 10, 11, 12, 13, 14, 15, 16],
+        [3, 4, 5, 6]
+    );
 
-    let c = 0x20;
-    for ei in 10..=13 {
-        for ej in 4..=5 {
-            schemes.push(Box::new(LzssDyn(
-                lzss::LzssDyn::new(ei, ej, c).context("couldn't create LzssDyn")?,
-            )));
+    for c in [0x00, 0x20] {
+        for ei in 8..=16 {
+            for ej in 3..=6 {
+                schemes.push(Box::new(LzssDyn(
+                    lzss::LzssDyn::new(ei, ej, c).context("couldn't create LzssDyn")?,
+                )));
+            }
         }
     }
 
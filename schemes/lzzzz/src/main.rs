@@ -1,5 +1,5 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{benchmark, detect::Format, Compressor, Decompressor, DescribeScheme};
 use lzzzz::lz4;
 use lzzzz::lz4_hc;
 use lzzzz::lz4f;
@@ -9,6 +9,7 @@ enum Lz4 {
     NormalBlock(i32),
     HcBlock(i32),
     Frame(Level),
+    FrameOptions(FrameOptions),
 }
 
 #[derive(Debug)]
@@ -18,6 +19,62 @@ enum Level {
     Max,
 }
 
+// keep the settings in sync with the lz4_flex scheme so that results can be joined row by row
+#[derive(Debug, Clone, Copy)]
+struct FrameOptions {
+    block_size: usize,
+    independent_blocks: bool,
+    content_checksum: bool,
+    block_checksum: bool,
+    content_size: bool,
+}
+
+impl FrameOptions {
+    fn settings(&self) -> String {
+        let mut settings = format!(
+            "frame / block size {} / {} blocks",
+            self.block_size,
+            if self.independent_blocks {
+                "independent"
+            } else {
+                "linked"
+            }
+        );
+        if self.content_checksum {
+            settings += " / content checksum";
+        }
+        if self.block_checksum {
+            settings += " / block checksum";
+        }
+        if self.content_size {
+            settings += " / content size";
+        }
+        settings
+    }
+
+    fn all() -> std::vec::Vec<Self> {
+        let mut all = vec![];
+        for block_size in [1 << 16, 1 << 18, 1 << 20, 1 << 22] {
+            for independent_blocks in [false, true] {
+                for content_checksum in [false, true] {
+                    for block_checksum in [false, true] {
+                        for content_size in [false, true] {
+                            all.push(Self {
+                                block_size,
+                                independent_blocks,
+                                content_checksum,
+                                block_checksum,
+                                content_size,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        all
+    }
+}
+
 impl DescribeScheme for Lz4 {
     fn name(&self) -> String {
         "lzzzz".to_string()
@@ -27,8 +84,15 @@ impl DescribeScheme for Lz4 {
             Lz4::NormalBlock(a) => format!("block / normal / acceleration {a}"),
             Lz4::HcBlock(a) => format!("block / hc / acceleration {a}"),
             Lz4::Frame(level) => format!("frame / {level:?}"),
+            Lz4::FrameOptions(options) => options.settings(),
         })
     }
+    fn format(&self) -> Option<Format> {
+        match self {
+            Lz4::NormalBlock(_) | Lz4::HcBlock(_) => None,
+            Lz4::Frame(_) | Lz4::FrameOptions(_) => Some(Format::Lz4Frame),
+        }
+    }
 }
 
 impl Compressor for Lz4 {
@@ -53,6 +117,35 @@ impl Compressor for Lz4 {
                 };
                 lz4f::compress_to_vec(data, &mut vec, &preferences).context("lzzzz error")?
             }
+            Lz4::FrameOptions(options) => {
+                let preferences = lz4f::PreferencesBuilder::new()
+                    .block_size(match options.block_size {
+                        0x1_0000 => lz4f::BlockSize::Max64KB,
+                        0x4_0000 => lz4f::BlockSize::Max256KB,
+                        0x10_0000 => lz4f::BlockSize::Max1MB,
+                        0x40_0000 => lz4f::BlockSize::Max4MB,
+                        size => anyhow::bail!("unsupported lz4 block size {size}"),
+                    })
+                    .block_mode(if options.independent_blocks {
+                        lz4f::BlockMode::Independent
+                    } else {
+                        lz4f::BlockMode::Linked
+                    })
+                    .content_checksum(if options.content_checksum {
+                        lz4f::ContentChecksum::Enabled
+                    } else {
+                        lz4f::ContentChecksum::Disabled
+                    })
+                    .block_checksum(if options.block_checksum {
+                        lz4f::BlockChecksum::Enabled
+                    } else {
+                        lz4f::BlockChecksum::Disabled
+                    })
+                    // any nonzero size makes lzzzz write the actual size, zero means unknown
+                    .content_size(usize::from(options.content_size))
+                    .build();
+                lz4f::compress_to_vec(data, &mut vec, &preferences).context("lzzzz error")?
+            }
         };
         anyhow::ensure!(len == vec.len());
         Ok(vec)
@@ -66,7 +159,7 @@ impl Decompressor for Lz4 {
                 let len = lz4::decompress(src, dst).context("lzzzz error")?;
                 anyhow::ensure!(len == dst.len(), "destination buffer length doesn't match");
             }
-            Lz4::Frame(_) => {
+            Lz4::Frame(_) | Lz4::FrameOptions(_) => {
                 let mut decompressor = lz4f::ReadDecompressor::new(src)
                     .context("couldn't initialize the lzzzz decompressor")?;
                 decompressor
@@ -89,5 +182,8 @@ fn main() -> anyhow::Result<()> {
         schemes.push(Lz4::NormalBlock(i));
         schemes.push(Lz4::HcBlock(i));
     }
+    for options in FrameOptions::all() {
+        schemes.push(Lz4::FrameOptions(options));
+    }
     benchmark(std::io::stdout(), schemes).context("benchmark failed")
 }
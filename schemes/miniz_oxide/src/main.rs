@@ -0,0 +1,69 @@
+use anyhow::Context as _;
+use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Deflate,
+    Zlib,
+}
+
+/// miniz_oxide without the flate2 `Read`/`Write` adapters.
+/// The settings match the flate2 (rust) scheme so that results can be joined row by row.
+struct MinizOxide {
+    format: Format,
+    level: u8,
+}
+
+impl DescribeScheme for MinizOxide {
+    fn name(&self) -> String {
+        "miniz_oxide".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(format!(
+            "{} / level {}",
+            format!("{:?}", self.format).to_lowercase(),
+            self.level
+        ))
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.format {
+            Format::Deflate => None,
+            Format::Zlib => Some(common::detect::Format::Zlib),
+        }
+    }
+}
+
+impl Compressor for MinizOxide {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        Ok(match self.format {
+            Format::Deflate => miniz_oxide::deflate::compress_to_vec(data, self.level),
+            Format::Zlib => miniz_oxide::deflate::compress_to_vec_zlib(data, self.level),
+        })
+    }
+}
+
+impl Decompressor for MinizOxide {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        // the whole input in one slice, so this is a single call to the core inflate state machine
+        let len = miniz_oxide::inflate::decompress_slice_iter_to_slice(
+            dst,
+            std::iter::once(src),
+            matches!(self.format, Format::Zlib),
+            false,
+        )
+        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
+        .context("miniz_oxide decompression failed")?;
+        anyhow::ensure!(len == dst.len(), "dst buffer length mismatch");
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut schemes = vec![];
+    for level in 0..=10 {
+        for format in [Format::Deflate, Format::Zlib] {
+            schemes.push(MinizOxide { format, level });
+        }
+    }
+    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+}
@@ -0,0 +1,16 @@
+use anyhow::Context as _;
+use common::{benchmark, plugin::load_plugin};
+
+/// Benchmarks the codecs of the plugins given as arguments, e.g.
+/// `cargo run --release -- ../../plugins/lz4_flex/target/release/liblz4_flex_plugin.so`.
+/// Without arguments there is nothing to do, so that `run.sh` still works.
+fn main() -> anyhow::Result<()> {
+    let mut schemes = vec![];
+    for path in std::env::args_os().skip(1) {
+        schemes.extend(load_plugin(&path)?);
+    }
+    if schemes.is_empty() {
+        return Ok(());
+    }
+    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+}
@@ -1,6 +1,11 @@
 #!/bin/sh
 set -e
 
+# with --ratio-only, only the compression ratios are measured (see RATIO_ONLY_VAR in common)
+if [ "$1" = "--ratio-only" ]; then
+  export BENCHMARK_RATIO_ONLY=1
+fi
+
 printf "%s\n" "scheme,settings,corpus,compression speed (MB/s),compression speed standard deviation (MB/s),decompression speed (MB/s),decompression speed standard deviation (MB/s),compression ratio"
 for scheme in $(find . -maxdepth 1 -type d -not -path "."); do
   cd "$scheme"
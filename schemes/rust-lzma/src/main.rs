@@ -1,5 +1,5 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{benchmark, detect::Format, Compressor, Decompressor, DescribeScheme};
 use std::io::Read;
 
 struct Lzma {
@@ -18,6 +18,9 @@ impl DescribeScheme for Lzma {
             Some(format!("preset {}", self.preset))
         }
     }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Xz)
+    }
 }
 
 impl Compressor for Lzma {
@@ -0,0 +1,118 @@
+use anyhow::Context as _;
+use common::{
+    benchmark, benchmark_decompression_only, detect::Format, Compressor, Decompressor,
+    DescribeScheme,
+};
+
+fn decompress(src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+    let len = ruzstd::decoding::FrameDecoder::new()
+        .decode_all(src, dst)
+        .context("ruzstd decompression failed")?;
+    anyhow::ensure!(len == dst.len(), "dst buffer length mismatch");
+    Ok(())
+}
+
+/// ruzstd decoding the output of libzstd, since its own encoder only implements the fastest levels.
+/// The settings match the zstd scheme so that results can be joined row by row.
+struct Libzstd {
+    level: i32,
+    /// Level 22 uses a larger window than ruzstd accepts, so it is capped at this.
+    window_log: Option<u32>,
+}
+
+/// ruzstd rejects frames with windows over 100 MiB, the largest window log below that.
+const MAX_WINDOW_LOG: u32 = 26;
+
+impl Libzstd {
+    fn new(level: i32) -> Self {
+        let window_log = (level >= 22).then_some(MAX_WINDOW_LOG);
+        Self { level, window_log }
+    }
+}
+
+impl DescribeScheme for Libzstd {
+    fn name(&self) -> String {
+        "ruzstd".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!("level {}", self.level);
+        if let Some(window_log) = self.window_log {
+            settings += &format!(" / window log {window_log}");
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Zstd)
+    }
+}
+
+impl Compressor for Libzstd {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut compressor =
+            zstd::bulk::Compressor::new(self.level).context("couldn't create zstd compressor")?;
+        if let Some(window_log) = self.window_log {
+            compressor
+                .set_parameter(zstd::stream::raw::CParameter::WindowLog(window_log))
+                .context("couldn't set zstd window log")?;
+        }
+        compressor.compress(data).context("zstd compression failed")
+    }
+}
+
+impl Decompressor for Libzstd {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress(src, dst)
+    }
+}
+
+/// The pure Rust encoder of ruzstd.
+#[derive(Debug, Clone, Copy)]
+enum Ruzstd {
+    Uncompressed,
+    Fastest,
+}
+
+impl DescribeScheme for Ruzstd {
+    fn name(&self) -> String {
+        "ruzstd".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(format!(
+            "ruzstd encoder / {}",
+            format!("{self:?}").to_lowercase()
+        ))
+    }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Zstd)
+    }
+}
+
+impl Compressor for Ruzstd {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let level = match self {
+            Ruzstd::Uncompressed => ruzstd::encoding::CompressionLevel::Uncompressed,
+            Ruzstd::Fastest => ruzstd::encoding::CompressionLevel::Fastest,
+        };
+        Ok(ruzstd::encoding::compress_to_vec(data, level))
+    }
+}
+
+impl Decompressor for Ruzstd {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress(src, dst)
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    benchmark(std::io::stdout(), [Ruzstd::Uncompressed, Ruzstd::Fastest])
+        .context("benchmark failed")?;
+
+    let mut schemes = vec![];
+    for level in [-50, -20, -15, -10, -5, -2, -1] {
+        schemes.push(Libzstd::new(level));
+    }
+    for level in 0..=22 {
+        schemes.push(Libzstd::new(level));
+    }
+    benchmark_decompression_only(std::io::stdout(), schemes).context("benchmark failed")
+}
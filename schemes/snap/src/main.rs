@@ -1,5 +1,7 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{
+    benchmark, benchmark_concurrency, detect::Format, Compressor, Decompressor, DescribeScheme,
+};
 use std::io::{Read, Write};
 
 #[derive(Debug)]
@@ -15,6 +17,12 @@ impl DescribeScheme for Snap {
     fn settings(&self) -> Option<String> {
         Some(format!("{self:?}"))
     }
+    fn format(&self) -> Option<Format> {
+        match self {
+            Snap::Raw => None,
+            Snap::Frame => Some(Format::SnappyFramed),
+        }
+    }
 }
 
 impl Compressor for Snap {
@@ -59,9 +67,27 @@ impl Decompressor for Snap {
             }
         }
     }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        match self {
+            // raw snappy starts with the decompressed length
+            Snap::Raw => snap::raw::Decoder::new()
+                .decompress_vec(src)
+                .context("snappy decompression failed"),
+            Snap::Frame => {
+                let mut decompressed = vec![];
+                snap::read::FrameDecoder::new(src)
+                    .read_to_end(&mut decompressed)
+                    .context("snappy decompression failed")?;
+                Ok(decompressed)
+            }
+        }
+    }
 }
 
 fn main() -> anyhow::Result<()> {
     let schemes = [Snap::Raw, Snap::Frame];
+    if std::env::args().any(|arg| arg == "--concurrency") {
+        return benchmark_concurrency(std::io::stdout(), schemes).context("benchmark failed");
+    }
     benchmark(std::io::stdout(), schemes).context("benchmark failed")
 }
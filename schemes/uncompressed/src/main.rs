@@ -1,5 +1,5 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{benchmark, benchmark_concurrency, Compressor, Decompressor, DescribeScheme};
 
 struct Uncompressed {}
 
@@ -31,5 +31,8 @@ impl Decompressor for Uncompressed {
 
 fn main() -> anyhow::Result<()> {
     let schemes = [Uncompressed {}];
+    if std::env::args().any(|arg| arg == "--concurrency") {
+        return benchmark_concurrency(std::io::stdout(), schemes).context("benchmark failed");
+    }
     benchmark(std::io::stdout(), schemes).context("benchmark failed")
 }
@@ -0,0 +1,403 @@
+use anyhow::Context as _;
+use common::{benchmark, thread_counts, Chunked, Compressor, Decompressor, DescribeScheme};
+use std::io::Read as _;
+
+#[derive(Clone, Copy)]
+struct Xz {
+    preset: u32,
+    threads: Option<u32>,
+}
+
+impl DescribeScheme for Xz {
+    fn name(&self) -> String {
+        "xz2".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        match self.threads {
+            None => Some(format!("preset {}", self.preset)),
+            Some(threads) => Some(format!("preset {} / threads {}", self.preset, threads)),
+        }
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        Some(common::detect::Format::Xz)
+    }
+    fn threads(&self) -> std::num::NonZeroUsize {
+        self.threads
+            .and_then(|threads| std::num::NonZeroUsize::new(threads.try_into().unwrap()))
+            .unwrap_or(std::num::NonZeroUsize::MIN)
+    }
+}
+
+impl Compressor for Xz {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut stream = match self.threads {
+            None => xz2::stream::Stream::new_easy_encoder(self.preset, xz2::stream::Check::Crc64),
+            Some(threads) => xz2::stream::MtStreamBuilder::new()
+                .preset(self.preset)
+                .threads(threads)
+                .check(xz2::stream::Check::Crc64)
+                .encoder(),
+        }
+        .context("couldn't create xz encoder")?;
+        let mut vec = std::vec::Vec::with_capacity(data.len() / 2);
+        loop {
+            let consumed: usize = stream.total_in().try_into().unwrap();
+            let status = stream
+                .process_vec(&data[consumed..], &mut vec, xz2::stream::Action::Finish)
+                .context("xz compression failed")?;
+            if status == xz2::stream::Status::StreamEnd {
+                return Ok(vec);
+            }
+            if vec.len() == vec.capacity() {
+                vec.reserve(vec.capacity().max(4096));
+            }
+        }
+    }
+}
+
+fn decompress(mut stream: xz2::stream::Stream, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+    let status = stream
+        .process(src, dst, xz2::stream::Action::Finish)
+        .context("xz decompression error")?;
+    anyhow::ensure!(
+        status == xz2::stream::Status::StreamEnd,
+        "xz decompression error: dst too short"
+    );
+    let actual_len: usize = stream.total_out().try_into().unwrap();
+    anyhow::ensure!(
+        actual_len == dst.len(),
+        "xz decompression error: dst too long"
+    );
+    Ok(())
+}
+
+fn decompress_to_end(stream: xz2::stream::Stream, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+    let mut decompressed = vec![];
+    xz2::read::XzDecoder::new_stream(src, stream)
+        .read_to_end(&mut decompressed)
+        .context("xz decompression error")?;
+    Ok(decompressed)
+}
+
+impl Decompressor for Xz {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress(
+            xz2::stream::Stream::new_stream_decoder(u64::MAX, 0)
+                .context("couldn't create xz decoder")?,
+            src,
+            dst,
+        )
+    }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        decompress_to_end(
+            xz2::stream::Stream::new_stream_decoder(u64::MAX, 0)
+                .context("couldn't create xz decoder")?,
+            src,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MatchFinder {
+    Hc3,
+    Hc4,
+    Bt2,
+    Bt4,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Xz,
+    // the legacy .lzma format, which has no filter chain and no integrity check
+    Lzma,
+}
+
+// lzma-sys doesn't bind the delta filter
+const LZMA_FILTER_DELTA: lzma_sys::lzma_vli = 0x03;
+const LZMA_DELTA_TYPE_BYTE: u32 = 0;
+
+#[repr(C)]
+struct DeltaOptions {
+    kind: u32,
+    dist: u32,
+    reserved_int: [u32; 8],
+    reserved_ptr: [*mut std::ffi::c_void; 2],
+}
+
+/// Custom LZMA options and filter chains, which neither rust-lzma nor xz2 fully expose,
+/// so the encoder is set up with lzma-sys directly.
+#[derive(Clone, Copy)]
+struct Filtered {
+    preset: u32,
+    dict_size: Option<u32>,
+    match_finder: Option<MatchFinder>,
+    nice_len: Option<u32>,
+    x86: bool,
+    delta: Option<u32>,
+    format: Format,
+}
+
+impl Filtered {
+    const fn new(preset: u32) -> Self {
+        Self {
+            preset,
+            dict_size: None,
+            match_finder: None,
+            nice_len: None,
+            x86: false,
+            delta: None,
+            format: Format::Xz,
+        }
+    }
+
+    fn lzma_options(&self) -> anyhow::Result<lzma_sys::lzma_options_lzma> {
+        // SAFETY: lzma_options_lzma is plain data, and lzma_lzma_preset initializes all fields
+        let mut options: lzma_sys::lzma_options_lzma = unsafe { std::mem::zeroed() };
+        let failed = unsafe { lzma_sys::lzma_lzma_preset(&mut options, self.preset) };
+        anyhow::ensure!(failed == 0, "unsupported lzma preset {}", self.preset);
+        if let Some(dict_size) = self.dict_size {
+            options.dict_size = dict_size;
+        }
+        if let Some(match_finder) = self.match_finder {
+            options.mf = match match_finder {
+                MatchFinder::Hc3 => lzma_sys::LZMA_MF_HC3,
+                MatchFinder::Hc4 => lzma_sys::LZMA_MF_HC4,
+                MatchFinder::Bt2 => lzma_sys::LZMA_MF_BT2,
+                MatchFinder::Bt4 => lzma_sys::LZMA_MF_BT4,
+            };
+        }
+        if let Some(nice_len) = self.nice_len {
+            options.nice_len = nice_len;
+        }
+        Ok(options)
+    }
+}
+
+impl DescribeScheme for Filtered {
+    fn name(&self) -> String {
+        "xz2".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!("preset {}", self.preset);
+        if let Some(dict_size) = self.dict_size {
+            settings += &format!(" / dict size {dict_size}");
+        }
+        if let Some(match_finder) = self.match_finder {
+            settings += &format!(
+                " / match finder {}",
+                format!("{match_finder:?}").to_lowercase()
+            );
+        }
+        if let Some(nice_len) = self.nice_len {
+            settings += &format!(" / nice len {nice_len}");
+        }
+        if let Some(dist) = self.delta {
+            settings += &format!(" / delta {dist}");
+        }
+        if self.x86 {
+            settings += " / x86";
+        }
+        if self.format == Format::Lzma {
+            settings += " / lzma";
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        Some(match self.format {
+            Format::Xz => common::detect::Format::Xz,
+            Format::Lzma => common::detect::Format::LzmaAlone,
+        })
+    }
+}
+
+impl Compressor for Filtered {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        let mut options = self.lzma_options()?;
+        let bound = unsafe { lzma_sys::lzma_stream_buffer_bound(data.len()) };
+        anyhow::ensure!(bound != 0, "input too large for xz");
+        let mut vec = std::vec::Vec::with_capacity(bound);
+        let mut len = 0;
+        let ret = match self.format {
+            Format::Xz => {
+                let mut delta = DeltaOptions {
+                    kind: LZMA_DELTA_TYPE_BYTE,
+                    dist: self.delta.unwrap_or(1),
+                    reserved_int: [0; 8],
+                    reserved_ptr: [std::ptr::null_mut(); 2],
+                };
+                let mut filters = vec![];
+                if self.delta.is_some() {
+                    filters.push(lzma_sys::lzma_filter {
+                        id: LZMA_FILTER_DELTA,
+                        options: (&mut delta as *mut DeltaOptions).cast(),
+                    });
+                }
+                if self.x86 {
+                    filters.push(lzma_sys::lzma_filter {
+                        id: lzma_sys::LZMA_FILTER_X86,
+                        options: std::ptr::null_mut(),
+                    });
+                }
+                filters.push(lzma_sys::lzma_filter {
+                    id: lzma_sys::LZMA_FILTER_LZMA2,
+                    options: (&mut options as *mut lzma_sys::lzma_options_lzma).cast(),
+                });
+                filters.push(lzma_sys::lzma_filter {
+                    id: lzma_sys::LZMA_VLI_UNKNOWN,
+                    options: std::ptr::null_mut(),
+                });
+                // SAFETY: the filter chain is terminated and its options outlive the call,
+                // and the output buffer has `bound` bytes of capacity
+                unsafe {
+                    lzma_sys::lzma_stream_buffer_encode(
+                        filters.as_mut_ptr(),
+                        lzma_sys::LZMA_CHECK_CRC64,
+                        std::ptr::null(),
+                        data.as_ptr(),
+                        data.len(),
+                        vec.as_mut_ptr(),
+                        &mut len,
+                        bound,
+                    )
+                }
+            }
+            Format::Lzma => {
+                anyhow::ensure!(
+                    !self.x86 && self.delta.is_none(),
+                    "the lzma format doesn't support filters"
+                );
+                // SAFETY: an all-zero lzma_stream is LZMA_STREAM_INIT, the input and output
+                // buffers outlive the stream, the output buffer has room for `avail_out` bytes
+                // after the `len` initialized so far, and the stream is ended before returning
+                unsafe {
+                    let mut stream: lzma_sys::lzma_stream = std::mem::zeroed();
+                    let mut ret = lzma_sys::lzma_alone_encoder(&mut stream, &options);
+                    if ret == lzma_sys::LZMA_OK {
+                        stream.next_in = data.as_ptr();
+                        stream.avail_in = data.len();
+                        // unlike lzma2, lzma has no uncompressed chunks, so incompressible data
+                        // can grow beyond the xz bound and the buffer has to grow with it
+                        loop {
+                            stream.next_out = vec.as_mut_ptr().add(len);
+                            stream.avail_out = vec.capacity() - len;
+                            ret = lzma_sys::lzma_code(&mut stream, lzma_sys::LZMA_FINISH);
+                            len = stream.total_out.try_into().unwrap();
+                            if ret != lzma_sys::LZMA_OK || stream.avail_out != 0 {
+                                break;
+                            }
+                            vec.set_len(len);
+                            vec.reserve(len / 8);
+                        }
+                    }
+                    lzma_sys::lzma_end(&mut stream);
+                    match ret {
+                        lzma_sys::LZMA_STREAM_END => lzma_sys::LZMA_OK,
+                        lzma_sys::LZMA_OK => lzma_sys::LZMA_BUF_ERROR,
+                        ret => ret,
+                    }
+                }
+            }
+        };
+        anyhow::ensure!(
+            ret == lzma_sys::LZMA_OK,
+            "xz compression failed: liblzma error {ret}"
+        );
+        // SAFETY: liblzma initialized the first `len` bytes
+        unsafe { vec.set_len(len) };
+        Ok(vec)
+    }
+}
+
+impl Filtered {
+    fn decoder(&self) -> anyhow::Result<xz2::stream::Stream> {
+        match self.format {
+            Format::Xz => xz2::stream::Stream::new_stream_decoder(u64::MAX, 0),
+            Format::Lzma => xz2::stream::Stream::new_lzma_decoder(u64::MAX),
+        }
+        .context("couldn't create xz decoder")
+    }
+}
+
+impl Decompressor for Filtered {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress(self.decoder()?, src, dst)
+    }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        decompress_to_end(self.decoder()?, src)
+    }
+}
+
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Xz {}
+impl Scheme for Chunked<Xz> {}
+impl Scheme for Filtered {}
+
+fn main() -> anyhow::Result<()> {
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
+    for preset in [1, 6, 9] {
+        schemes.push(Box::new(Xz {
+            preset,
+            threads: None,
+        }));
+    }
+    // native multithreading (lzma_stream_encoder_mt) vs. naive chunking with the same number of threads
+    for preset in [1, 6, 9] {
+        for threads in thread_counts() {
+            schemes.push(Box::new(Xz {
+                preset,
+                threads: Some(threads.get().try_into().unwrap()),
+            }));
+            schemes.push(Box::new(Chunked {
+                inner: Xz {
+                    preset,
+                    threads: None,
+                },
+                chunk_size: std::num::NonZeroUsize::new(1 << 24).unwrap(),
+                threads,
+            }));
+        }
+    }
+    for preset in [1, 6, 9] {
+        schemes.push(Box::new(Filtered {
+            format: Format::Lzma,
+            ..Filtered::new(preset)
+        }));
+        // BCJ mostly matters for executables, e.g. ooffice and mozilla in silesia
+        schemes.push(Box::new(Filtered {
+            x86: true,
+            ..Filtered::new(preset)
+        }));
+    }
+    for dict_size in [1 << 20, 1 << 22, 1 << 24, 1 << 26] {
+        schemes.push(Box::new(Filtered {
+            dict_size: Some(dict_size),
+            ..Filtered::new(6)
+        }));
+    }
+    for preset in [1, 6] {
+        for match_finder in [
+            MatchFinder::Hc3,
+            MatchFinder::Hc4,
+            MatchFinder::Bt2,
+            MatchFinder::Bt4,
+        ] {
+            schemes.push(Box::new(Filtered {
+                match_finder: Some(match_finder),
+                ..Filtered::new(preset)
+            }));
+        }
+    }
+    for nice_len in [8, 16, 32, 64, 128, 273] {
+        schemes.push(Box::new(Filtered {
+            nice_len: Some(nice_len),
+            ..Filtered::new(6)
+        }));
+    }
+    for dist in [1, 2, 4, 8] {
+        schemes.push(Box::new(Filtered {
+            delta: Some(dist),
+            ..Filtered::new(6)
+        }));
+    }
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
+}
@@ -1,8 +1,48 @@
 use anyhow::Context as _;
 use common::{benchmark, Compressor, Decompressor, DescribeScheme};
 
+// yazi::CompressionStrategy doesn't implement Debug
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+    Default,
+    Rle,
+    Filtered,
+    Static,
+    Huffman,
+}
+
+impl From<Strategy> for yazi::CompressionStrategy {
+    fn from(strategy: Strategy) -> Self {
+        match strategy {
+            Strategy::Default => yazi::CompressionStrategy::Default,
+            Strategy::Rle => yazi::CompressionStrategy::RLE,
+            Strategy::Filtered => yazi::CompressionStrategy::Filtered,
+            Strategy::Static => yazi::CompressionStrategy::Static,
+            Strategy::Huffman => yazi::CompressionStrategy::Huffman,
+        }
+    }
+}
+
 struct Yazi {
     level: yazi::CompressionLevel,
+    format: yazi::Format,
+    strategy: Strategy,
+}
+
+impl Yazi {
+    fn new_encoder(&self) -> Box<yazi::Encoder> {
+        let mut encoder = yazi::Encoder::boxed();
+        encoder.set_format(self.format);
+        encoder.set_level(self.level);
+        encoder.set_strategy(self.strategy.into());
+        encoder
+    }
+
+    fn new_decoder(&self) -> yazi::Decoder {
+        let mut decoder = yazi::Decoder::new();
+        decoder.set_format(self.format);
+        decoder
+    }
 }
 
 impl DescribeScheme for Yazi {
@@ -10,59 +50,151 @@ impl DescribeScheme for Yazi {
         "yazi".to_string()
     }
     fn settings(&self) -> Option<String> {
-        Some(format!("{:?}", self.level))
+        let mut settings = format!("{:?}", self.level);
+        if self.format == yazi::Format::Zlib {
+            settings += " / Zlib";
+        }
+        if self.strategy != Strategy::Default {
+            settings += &format!(" / strategy {:?}", self.strategy);
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        (self.format == yazi::Format::Zlib).then_some(common::detect::Format::Zlib)
     }
 }
 
+fn compress(encoder: &mut yazi::Encoder, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+    let mut vec = vec![];
+    let mut stream = encoder.stream_into_vec(&mut vec);
+    stream
+        .write(data)
+        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
+        .context("yazi compression failed")?;
+    stream
+        .finish()
+        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
+        .context("yazi compression failed")?;
+    Ok(vec)
+}
+
+fn decompress(decoder: &mut yazi::Decoder, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+    let expected_len = dst.len();
+    let mut stream = decoder.stream_into_buf(dst);
+    stream
+        .write(src)
+        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
+        .context("yazi decompression error")?;
+    let (actual_len, _checksum) = stream
+        .finish()
+        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
+        .context("yazi decompression error")?;
+    let actual_len: usize = actual_len.try_into().unwrap();
+    anyhow::ensure!(
+        actual_len == expected_len,
+        "yazi decompression error: compressed data too short"
+    );
+    Ok(())
+}
+
 impl Compressor for Yazi {
     fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
-        yazi::compress(data, yazi::Format::Raw, self.level)
-            .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
-            .context("yazi compression failed")
+        compress(&mut self.new_encoder(), data)
     }
 }
 
 impl Decompressor for Yazi {
     fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
-        let expected_len = dst.len();
-        let mut decoder = yazi::Decoder::new();
-        let mut stream = decoder.stream_into_buf(dst);
-        stream
-            .write(src)
-            .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
-            .context("yazi decompression error")?;
-        let (actual_len, _checksum) = stream
-            .finish()
-            .map_err(|e| anyhow::Error::msg(format!("{e:?}")))
-            .context("yazi decompression error")?;
-        let actual_len: usize = actual_len.try_into().unwrap();
-        anyhow::ensure!(
-            actual_len == expected_len,
-            "yazi decompression error: compressed data too short"
-        );
-        Ok(())
+        decompress(&mut self.new_decoder(), src, dst)
     }
 }
 
+/// Keeps one encoder and one decoder context for all runs instead of creating new ones.
+/// The locks are uncontended except in the ratio-only mode, which isn't timed.
+struct Reused {
+    settings: Yazi,
+    encoder: std::sync::Mutex<Box<yazi::Encoder>>,
+    decoder: std::sync::Mutex<yazi::Decoder>,
+}
+
+impl Reused {
+    fn new(settings: Yazi) -> Self {
+        Self {
+            encoder: std::sync::Mutex::new(settings.new_encoder()),
+            decoder: std::sync::Mutex::new(settings.new_decoder()),
+            settings,
+        }
+    }
+}
+
+impl DescribeScheme for Reused {
+    fn name(&self) -> String {
+        self.settings.name()
+    }
+    fn settings(&self) -> Option<String> {
+        Some(self.settings.settings()? + " / reused context")
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        self.settings.format()
+    }
+}
+
+impl Compressor for Reused {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        compress(&mut self.encoder.lock().expect("encoder poisoned"), data)
+    }
+}
+
+impl Decompressor for Reused {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        decompress(
+            &mut self.decoder.lock().expect("decoder poisoned"),
+            src,
+            dst,
+        )
+    }
+}
+
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Yazi {}
+impl Scheme for Reused {}
+
 fn main() -> anyhow::Result<()> {
-    let mut schemes = vec![
-        Yazi {
-            level: yazi::CompressionLevel::None,
-        },
-        Yazi {
-            level: yazi::CompressionLevel::BestSpeed,
-        },
-        Yazi {
-            level: yazi::CompressionLevel::Default,
-        },
-        Yazi {
-            level: yazi::CompressionLevel::BestSize,
-        },
+    let mut levels = vec![
+        yazi::CompressionLevel::None,
+        yazi::CompressionLevel::BestSpeed,
+        yazi::CompressionLevel::Default,
+        yazi::CompressionLevel::BestSize,
     ];
     for level in 1..=10 {
-        schemes.push(Yazi {
-            level: yazi::CompressionLevel::Specific(level),
-        });
+        levels.push(yazi::CompressionLevel::Specific(level));
+    }
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
+    for &level in levels.iter() {
+        for format in [yazi::Format::Raw, yazi::Format::Zlib] {
+            schemes.push(Box::new(Yazi {
+                level,
+                format,
+                strategy: Strategy::Default,
+            }));
+        }
+        for strategy in [
+            Strategy::Rle,
+            Strategy::Filtered,
+            Strategy::Static,
+            Strategy::Huffman,
+        ] {
+            schemes.push(Box::new(Yazi {
+                level,
+                format: yazi::Format::Raw,
+                strategy,
+            }));
+        }
+        schemes.push(Box::new(Reused::new(Yazi {
+            level,
+            format: yazi::Format::Raw,
+            strategy: Strategy::Default,
+        })));
     }
-    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
 }
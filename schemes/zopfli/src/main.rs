@@ -1,14 +1,52 @@
 use anyhow::Context as _;
 use common::{benchmark_compression_only, Compressor, DescribeScheme};
 
-struct Zopfli;
+// keep the settings in sync with the zopfli-rs scheme so that results can be joined row by row
+struct Zopfli {
+    format: Format,
+    iterations: u8,
+    // 0 means unlimited, 1 disables block splitting
+    block_splits: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Deflate,
+    Gzip,
+    Zlib,
+}
+
+impl From<Format> for zopfli::Format {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Deflate => zopfli::Format::Deflate,
+            Format::Gzip => zopfli::Format::Gzip,
+            Format::Zlib => zopfli::Format::Zlib,
+        }
+    }
+}
 
 impl DescribeScheme for Zopfli {
     fn name(&self) -> String {
         "zopfli".to_string()
     }
     fn settings(&self) -> Option<String> {
-        None
+        Some(format!(
+            "{} / iterations {} / block splits {}",
+            format!("{:?}", self.format).to_lowercase(),
+            self.iterations,
+            match self.block_splits {
+                0 => "unlimited".to_string(),
+                b => b.to_string(),
+            }
+        ))
+    }
+    fn format(&self) -> Option<common::detect::Format> {
+        match self.format {
+            Format::Deflate => None,
+            Format::Gzip => Some(common::detect::Format::Gzip),
+            Format::Zlib => Some(common::detect::Format::Zlib),
+        }
     }
 }
 
@@ -17,10 +55,13 @@ impl Compressor for Zopfli {
         let mut vec = vec![];
         zopfli::compress(
             &zopfli::Options {
-                iteration_count: 5.try_into().unwrap(),
-                maximum_block_splits: 15,
+                iteration_count: self
+                    .iterations
+                    .try_into()
+                    .context("zopfli needs at least one iteration")?,
+                maximum_block_splits: self.block_splits,
             },
-            &zopfli::Format::Deflate,
+            &self.format.into(),
             data,
             &mut vec,
         )
@@ -30,6 +71,25 @@ impl Compressor for Zopfli {
 }
 
 fn main() -> anyhow::Result<()> {
-    let schemes = [Zopfli];
+    let mut schemes = vec![];
+    let standard = |iterations, block_splits| Zopfli {
+        format: Format::Deflate,
+        iterations,
+        block_splits,
+    };
+    // sweep each parameter around the previous default (5 iterations, 15 block splits) instead of
+    // the full grid, zopfli is slow enough as it is
+    for iterations in [1, 5, 15, 50] {
+        schemes.push(standard(iterations, 15));
+    }
+    for block_splits in [0, 1, 5, 50] {
+        schemes.push(standard(5, block_splits));
+    }
+    for format in [Format::Gzip, Format::Zlib] {
+        schemes.push(Zopfli {
+            format,
+            ..standard(5, 15)
+        });
+    }
     benchmark_compression_only(std::io::stdout(), schemes).context("benchmark failed")
 }
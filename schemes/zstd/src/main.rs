@@ -1,8 +1,13 @@
 use anyhow::Context as _;
-use common::{benchmark, Compressor, Decompressor, DescribeScheme};
+use common::{
+    benchmark, benchmark_concurrency, detect::Format, thread_counts, Chunked, Compressor,
+    Decompressor, DescribeScheme,
+};
 
+#[derive(Clone, Copy)]
 struct Zstd {
     level: i32,
+    workers: u32,
 }
 
 impl DescribeScheme for Zstd {
@@ -10,13 +15,33 @@ impl DescribeScheme for Zstd {
         "zstd".to_string()
     }
     fn settings(&self) -> Option<String> {
-        Some(format!("level {}", self.level))
+        if self.workers == 0 {
+            Some(format!("level {}", self.level))
+        } else {
+            Some(format!("level {} / threads {}", self.level, self.workers))
+        }
+    }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Zstd)
+    }
+    fn threads(&self) -> std::num::NonZeroUsize {
+        // no workers compresses on the calling thread
+        std::num::NonZeroUsize::new(self.workers.try_into().unwrap())
+            .unwrap_or(std::num::NonZeroUsize::MIN)
     }
 }
 
 impl Compressor for Zstd {
     fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
-        zstd::bulk::compress(data, self.level).context("zstd compression failed")
+        if self.workers == 0 {
+            return zstd::bulk::compress(data, self.level).context("zstd compression failed");
+        }
+        let mut compressor =
+            zstd::bulk::Compressor::new(self.level).context("couldn't create zstd compressor")?;
+        compressor
+            .set_parameter(zstd::stream::raw::CParameter::NbWorkers(self.workers))
+            .context("couldn't set zstd worker count")?;
+        compressor.compress(data).context("zstd compression failed")
     }
 }
 
@@ -27,15 +52,180 @@ impl Decompressor for Zstd {
         anyhow::ensure!(len == dst.len(), "dst buffer length mismatch");
         Ok(())
     }
+    fn decompress(&self, src: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        // the streaming decoder, since the frame doesn't have to record the content size
+        zstd::stream::decode_all(src).context("zstd decompression failed")
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ZstdAdvanced {
+    level: i32,
+    window_log: Option<u32>,
+    long_distance_matching: bool,
+    strategy: Option<zstd::zstd_safe::Strategy>,
+    checksum: bool,
+    content_size: bool,
+}
+
+impl ZstdAdvanced {
+    const fn new(level: i32) -> Self {
+        // the defaults of libzstd
+        Self {
+            level,
+            window_log: None,
+            long_distance_matching: false,
+            strategy: None,
+            checksum: false,
+            content_size: true,
+        }
+    }
+}
+
+impl DescribeScheme for ZstdAdvanced {
+    fn name(&self) -> String {
+        "zstd".to_string()
+    }
+    fn settings(&self) -> Option<String> {
+        let mut settings = format!("level {}", self.level);
+        if let Some(window_log) = self.window_log {
+            settings += &format!(" / window log {window_log}");
+        }
+        if self.long_distance_matching {
+            settings += " / long distance matching";
+        }
+        if let Some(strategy) = self.strategy {
+            settings += &format!(
+                " / strategy {}",
+                format!("{strategy:?}").trim_start_matches("ZSTD_")
+            );
+        }
+        if self.checksum {
+            settings += " / checksum";
+        }
+        if !self.content_size {
+            settings += " / no content size";
+        }
+        Some(settings)
+    }
+    fn format(&self) -> Option<Format> {
+        Some(Format::Zstd)
+    }
+}
+
+impl Compressor for ZstdAdvanced {
+    fn compress(&self, data: &[u8]) -> anyhow::Result<std::vec::Vec<u8>> {
+        use zstd::stream::raw::CParameter;
+        let mut compressor =
+            zstd::bulk::Compressor::new(self.level).context("couldn't create zstd compressor")?;
+        let mut parameters = vec![
+            CParameter::EnableLongDistanceMatching(self.long_distance_matching),
+            CParameter::ChecksumFlag(self.checksum),
+            CParameter::ContentSizeFlag(self.content_size),
+        ];
+        if let Some(window_log) = self.window_log {
+            parameters.push(CParameter::WindowLog(window_log));
+        }
+        if let Some(strategy) = self.strategy {
+            parameters.push(CParameter::Strategy(strategy));
+        }
+        for parameter in parameters {
+            compressor
+                .set_parameter(parameter)
+                .context("couldn't set zstd compression parameter")?;
+        }
+        compressor.compress(data).context("zstd compression failed")
+    }
+}
+
+impl Decompressor for ZstdAdvanced {
+    fn decompress_to(&self, src: &[u8], dst: &mut [u8]) -> anyhow::Result<()> {
+        let len =
+            zstd::bulk::decompress_to_buffer(src, dst).context("zstd decompression failed")?;
+        anyhow::ensure!(len == dst.len(), "dst buffer length mismatch");
+        Ok(())
+    }
 }
 
+trait Scheme: Compressor + Decompressor + DescribeScheme + Sync {}
+impl Scheme for Zstd {}
+impl Scheme for Chunked<Zstd> {}
+impl Scheme for ZstdAdvanced {}
+
 fn main() -> anyhow::Result<()> {
-    let mut schemes = vec![];
+    let mut schemes: std::vec::Vec<Box<dyn Scheme>> = vec![];
     for i in [-50, -20, -15, -10, -5, -2, -1] {
-        schemes.push(Zstd { level: i });
+        schemes.push(Box::new(Zstd {
+            level: i,
+            workers: 0,
+        }));
     }
     for i in 0..=22 {
-        schemes.push(Zstd { level: i });
+        schemes.push(Box::new(Zstd {
+            level: i,
+            workers: 0,
+        }));
+    }
+    if std::env::args().any(|arg| arg == "--concurrency") {
+        return benchmark_concurrency::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes)
+            .context("benchmark failed");
+    }
+    // native multithreading (zstdmt) vs. naive chunking with the same number of threads
+    for level in [1, 3, 9, 19] {
+        for threads in thread_counts() {
+            schemes.push(Box::new(Zstd {
+                level,
+                workers: threads.get().try_into().unwrap(),
+            }));
+            schemes.push(Box::new(Chunked {
+                inner: Zstd { level, workers: 0 },
+                chunk_size: std::num::NonZeroUsize::new(1 << 22).unwrap(),
+                threads,
+            }));
+        }
+    }
+    // advanced parameters, mostly relevant for large inputs with long-distance repetitions
+    for level in [3, 9, 19] {
+        for window_log in [10, 14, 17, 20, 22, 24, 25, 26, 27] {
+            for long_distance_matching in [false, true] {
+                schemes.push(Box::new(ZstdAdvanced {
+                    window_log: Some(window_log),
+                    long_distance_matching,
+                    ..ZstdAdvanced::new(level)
+                }));
+            }
+        }
+        schemes.push(Box::new(ZstdAdvanced {
+            long_distance_matching: true,
+            ..ZstdAdvanced::new(level)
+        }));
+        schemes.push(Box::new(ZstdAdvanced {
+            checksum: true,
+            ..ZstdAdvanced::new(level)
+        }));
+        schemes.push(Box::new(ZstdAdvanced {
+            content_size: false,
+            ..ZstdAdvanced::new(level)
+        }));
+    }
+    {
+        use zstd::zstd_safe::Strategy;
+        for strategy in [
+            Strategy::ZSTD_fast,
+            Strategy::ZSTD_dfast,
+            Strategy::ZSTD_greedy,
+            Strategy::ZSTD_lazy,
+            Strategy::ZSTD_lazy2,
+            Strategy::ZSTD_btlazy2,
+            Strategy::ZSTD_btopt,
+            Strategy::ZSTD_btultra,
+            Strategy::ZSTD_btultra2,
+        ] {
+            schemes.push(Box::new(ZstdAdvanced {
+                strategy: Some(strategy),
+                ..ZstdAdvanced::new(9)
+            }));
+        }
     }
-    benchmark(std::io::stdout(), schemes).context("benchmark failed")
+    benchmark::<_, _, dyn Scheme, _, _>(std::io::stdout(), schemes).context("benchmark failed")
 }
@@ -0,0 +1,353 @@
+use anyhow::Context as _;
+
+const MIN_MATCH: usize = 4;
+const HASH_BITS: u32 = 20;
+
+struct Profile {
+    size: usize,
+    histogram: [u64; 256],
+    order0_entropy: f64,
+    order1_entropy: f64,
+    // indexed by floor(log2(distance)): (number of matches, number of matched bytes)
+    distances: [(u64, u64); 64],
+    lz77_bits: f64,
+}
+
+/// The compression ratio of coding every byte in `bits`, `None` if that would be free (constant or
+/// empty data), where there is no finite bound.
+fn ratio_bound(bits: f64) -> Option<f64> {
+    (bits > 0.0).then(|| 8.0 / bits)
+}
+
+impl Profile {
+    fn order0_bound(&self) -> Option<f64> {
+        ratio_bound(self.order0_entropy)
+    }
+    fn order1_bound(&self) -> Option<f64> {
+        ratio_bound(self.order1_entropy)
+    }
+    fn lz77_bound(&self) -> Option<f64> {
+        (self.size > 0)
+            .then(|| ratio_bound(self.lz77_bits / self.size as f64))
+            .flatten()
+    }
+    /// The share of bytes covered by matches, `None` for empty data.
+    fn matched(&self) -> Option<f64> {
+        let matched: u64 = self.distances.iter().map(|(_, bytes)| bytes).sum();
+        (self.size > 0).then(|| matched as f64 / self.size as f64)
+    }
+}
+
+/// Entropy in bits per symbol of a distribution given by its counts.
+fn entropy<'a, I: IntoIterator<Item = &'a u64>>(counts: I) -> f64 {
+    let counts: std::vec::Vec<u64> = counts.into_iter().copied().filter(|&c| c > 0).collect();
+    let total: u64 = counts.iter().sum();
+    counts
+        .iter()
+        .map(|&c| {
+            let p = c as f64 / total as f64;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+fn order1_entropy(data: &[u8]) -> f64 {
+    let mut counts = vec![[0u64; 256]; 256];
+    for pair in data.windows(2) {
+        counts[pair[0] as usize][pair[1] as usize] += 1;
+    }
+    let transitions = data.len().saturating_sub(1) as f64;
+    if transitions == 0.0 {
+        return 0.0;
+    }
+    counts
+        .iter()
+        .map(|next| {
+            let context: u64 = next.iter().sum();
+            (context as f64 / transitions) * entropy(next)
+        })
+        .sum()
+}
+
+/// Length of the Elias gamma code for `x >= 1`.
+fn gamma_bits(x: usize) -> f64 {
+    (2 * x.ilog2() + 1) as f64
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let v = u32::from_le_bytes(bytes[..MIN_MATCH].try_into().unwrap());
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Greedy LZ77 parse with unlimited window, keeping only the most recent position for each hash.
+/// Returns the distance distribution of the matches and an estimated size in bits, with literals
+/// coded at their order-0 entropy and match lengths and distances coded with Elias gamma codes.
+fn lz77(data: &[u8]) -> ([(u64, u64); 64], f64) {
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut distances = [(0u64, 0u64); 64];
+    let mut literals = [0u64; 256];
+    let mut match_bits = 0.0;
+    let mut i = 0;
+    while i + MIN_MATCH <= data.len() {
+        let h = hash(&data[i..]);
+        let candidate = table[h];
+        table[h] = i;
+        if candidate != usize::MAX
+            && data[candidate..candidate + MIN_MATCH] == data[i..i + MIN_MATCH]
+        {
+            let mut len = MIN_MATCH;
+            while i + len < data.len() && data[candidate + len] == data[i + len] {
+                len += 1;
+            }
+            let distance = i - candidate;
+            let bucket = &mut distances[distance.ilog2() as usize];
+            bucket.0 += 1;
+            bucket.1 += len as u64;
+            match_bits += 1.0 + gamma_bits(len - MIN_MATCH + 1) + gamma_bits(distance);
+            for j in (i + 1)..(i + len).min(data.len() + 1 - MIN_MATCH) {
+                table[hash(&data[j..])] = j;
+            }
+            i += len;
+        } else {
+            literals[data[i] as usize] += 1;
+            i += 1;
+        }
+    }
+    for &byte in &data[i..] {
+        literals[byte as usize] += 1;
+    }
+    let literal_count: u64 = literals.iter().sum();
+    let literal_bits = literal_count as f64 * (1.0 + entropy(&literals));
+    (distances, match_bits + literal_bits)
+}
+
+fn profile(data: &[u8]) -> Profile {
+    let mut histogram = [0u64; 256];
+    for &byte in data {
+        histogram[byte as usize] += 1;
+    }
+    let (distances, lz77_bits) = lz77(data);
+    Profile {
+        size: data.len(),
+        order0_entropy: entropy(&histogram),
+        order1_entropy: order1_entropy(data),
+        histogram,
+        distances,
+        lz77_bits,
+    }
+}
+
+struct Member {
+    corpus: &'static str,
+    // None for the whole corpus
+    file: Option<String>,
+    profile: Profile,
+}
+
+fn profile_corpora(with_files: bool) -> anyhow::Result<std::vec::Vec<Member>> {
+    let mut members = vec![];
+    for corpus in common::CORPUS_FILES.iter() {
+        let mut all = vec![];
+        for file in corpus.files {
+            let data = common::read_corpus_data(common::CORPORA_BASE, [*file])
+                .with_context(|| format!("couldn't read {} corpus", corpus.name))?;
+            if with_files {
+                members.push(Member {
+                    corpus: corpus.name,
+                    file: Some(file.to_string()),
+                    profile: profile(&data),
+                });
+            }
+            all.extend(data);
+        }
+        members.push(Member {
+            corpus: corpus.name,
+            file: None,
+            profile: profile(&all),
+        });
+    }
+    for corpus in common::synthetic::corpora() {
+        members.push(Member {
+            corpus: corpus.name,
+            file: None,
+            profile: profile(&corpus.data),
+        });
+    }
+    Ok(members)
+}
+
+#[derive(serde::Serialize)]
+struct FileRow<'a> {
+    corpus: &'a str,
+    file: Option<&'a str>,
+    #[serde(rename = "size (bytes)")]
+    size: usize,
+    #[serde(rename = "order-0 entropy (bits/byte)")]
+    order0_entropy: f64,
+    #[serde(rename = "order-1 entropy (bits/byte)")]
+    order1_entropy: f64,
+    #[serde(rename = "matched bytes (share)")]
+    matched: Option<f64>,
+    #[serde(rename = "order-0 bound (ratio)")]
+    order0_bound: Option<f64>,
+    #[serde(rename = "order-1 bound (ratio)")]
+    order1_bound: Option<f64>,
+    #[serde(rename = "lz77 estimate (ratio)")]
+    lz77_bound: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+struct HistogramRow<'a> {
+    corpus: &'a str,
+    file: Option<&'a str>,
+    byte: u8,
+    count: u64,
+}
+
+#[derive(serde::Serialize)]
+struct DistanceRow<'a> {
+    corpus: &'a str,
+    file: Option<&'a str>,
+    #[serde(rename = "distance from")]
+    distance_from: u64,
+    #[serde(rename = "distance to")]
+    distance_to: u64,
+    matches: u64,
+    #[serde(rename = "matched bytes")]
+    matched_bytes: u64,
+    #[serde(rename = "matched bytes (share)")]
+    matched: f64,
+}
+
+#[derive(serde::Serialize)]
+struct GapRow<'a> {
+    scheme: &'a str,
+    settings: &'a str,
+    corpus: &'a str,
+    #[serde(rename = "compression ratio")]
+    compression_ratio: f64,
+    #[serde(rename = "share of order-0 bound")]
+    order0: Option<f64>,
+    #[serde(rename = "share of order-1 bound")]
+    order1: Option<f64>,
+    #[serde(rename = "share of lz77 estimate")]
+    lz77: Option<f64>,
+}
+
+fn print_files<W: std::io::Write>(writer: &mut csv::Writer<W>) -> anyhow::Result<()> {
+    for member in profile_corpora(true)? {
+        let profile = &member.profile;
+        writer.serialize(FileRow {
+            corpus: member.corpus,
+            file: member.file.as_deref(),
+            size: profile.size,
+            order0_entropy: profile.order0_entropy,
+            order1_entropy: profile.order1_entropy,
+            matched: profile.matched(),
+            order0_bound: profile.order0_bound(),
+            order1_bound: profile.order1_bound(),
+            lz77_bound: profile.lz77_bound(),
+        })?;
+    }
+    Ok(())
+}
+
+fn print_histograms<W: std::io::Write>(writer: &mut csv::Writer<W>) -> anyhow::Result<()> {
+    for member in profile_corpora(true)? {
+        for (byte, &count) in member.profile.histogram.iter().enumerate() {
+            writer.serialize(HistogramRow {
+                corpus: member.corpus,
+                file: member.file.as_deref(),
+                byte: byte.try_into().unwrap(),
+                count,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn print_distances<W: std::io::Write>(writer: &mut csv::Writer<W>) -> anyhow::Result<()> {
+    for member in profile_corpora(true)? {
+        for (bucket, &(matches, matched_bytes)) in member.profile.distances.iter().enumerate() {
+            if matches == 0 {
+                continue;
+            }
+            writer.serialize(DistanceRow {
+                corpus: member.corpus,
+                file: member.file.as_deref(),
+                distance_from: 1 << bucket,
+                distance_to: (1 << bucket) * 2 - 1,
+                matches,
+                matched_bytes,
+                matched: matched_bytes as f64 / member.profile.size as f64,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn print_gaps<W: std::io::Write>(writer: &mut csv::Writer<W>, results: &str) -> anyhow::Result<()> {
+    let profiles = profile_corpora(false)?;
+    let mut reader = csv::Reader::from_path(results)
+        .with_context(|| format!("couldn't open results file {results}"))?;
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .with_context(|| format!("results file has no column '{name}'"))
+    };
+    let (scheme, settings, corpus, ratio) = (
+        column("scheme")?,
+        column("settings")?,
+        column("corpus")?,
+        column("compression ratio")?,
+    );
+    for record in reader.records() {
+        let record = record.context("couldn't read results file")?;
+        let Some(member) = profiles.iter().find(|m| m.corpus == &record[corpus]) else {
+            continue;
+        };
+        let compression_ratio: f64 = record[ratio]
+            .parse()
+            .context("couldn't parse compression ratio")?;
+        writer.serialize(GapRow {
+            scheme: &record[scheme],
+            settings: &record[settings],
+            corpus: &record[corpus],
+            compression_ratio,
+            order0: member
+                .profile
+                .order0_bound()
+                .map(|bound| compression_ratio / bound),
+            order1: member
+                .profile
+                .order1_bound()
+                .map(|bound| compression_ratio / bound),
+            lz77: member
+                .profile
+                .lz77_bound()
+                .map(|bound| compression_ratio / bound),
+        })?;
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: std::vec::Vec<String> = std::env::args().skip(1).collect();
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    match args.first().map(String::as_str) {
+        None | Some("files") => print_files(&mut writer),
+        Some("histogram") => print_histograms(&mut writer),
+        Some("distances") => print_distances(&mut writer),
+        Some("gaps") => print_gaps(
+            &mut writer,
+            args.get(1).map_or("../../results.csv", String::as_str),
+        ),
+        Some(command) => Err(anyhow::Error::msg(format!(
+            "unknown command '{command}', expected files, histogram, distances or gaps"
+        ))),
+    }
+    .context("analysis failed")?;
+    writer.flush().context("couldn't write to stdout")
+}
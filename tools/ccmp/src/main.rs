@@ -0,0 +1,320 @@
+use anyhow::Context as _;
+use common::ccmp::{Command, COMMAND_VAR, INPUT_VAR, OUTPUT_VAR, SCHEME_VAR, SETTINGS_VAR};
+use common::detect::Format;
+
+mod probe;
+
+const USAGE: &str = "usage:
+  ccmp list [--crate <crate>]
+  ccmp detect [--crate <crate>] [<input>]
+  ccmp compress --scheme <name> [--settings <settings>] [--crate <crate>] <input> <output>
+  ccmp decompress [--crate <crate>] <input> <output>
+  ccmp decompress --detect <input> <output>
+  ccmp roundtrip --scheme <name> [--settings <settings>] [--crate <crate>] <input>
+  ccmp benchmark --scheme <name> [--settings <settings>] [--crate <crate>] <input>
+  ccmp probe [--crate <crate>] <input>";
+
+/// The crates in `schemes` that have schemes of each name, for schemes of the same name in
+/// several crates the one with the right settings is found by trying them in order.
+const SCHEME_CRATES: &[(&str, &[&str])] = &[
+    ("brotli", &["brotli"]),
+    ("brotli (cli)", &["cli"]),
+    ("brotlic", &["brotlic"]),
+    ("bzip2", &["bzip2", "libbz2-rs"]),
+    ("bzip2 (cli)", &["cli"]),
+    ("bzip2 (libbz2-rs)", &["libbz2-rs"]),
+    ("deflate", &["deflate"]),
+    ("flate2 (rust)", &["flate2-rust"]),
+    ("flate2 (zlib-ng)", &["flate2-zlib-ng"]),
+    ("flate2 (zlib-rs)", &["flate2-zlib-rs"]),
+    ("gzip (cli)", &["cli"]),
+    ("huffman", &["baseline"]),
+    ("libdeflate", &["libdeflate"]),
+    ("lz4 (cli)", &["cli"]),
+    ("lz4_flex", &["lz4_flex", "lz4_flex_unsafe", "chunked"]),
+    ("lzma-rs", &["lzma-rs"]),
+    ("lzo1x-1", &["lzo1x"]),
+    ("lzss (dyn)", &["lzss"]),
+    ("lzss (static)", &["lzss"]),
+    ("lzzzz", &["lzzzz"]),
+    ("miniz_oxide", &["miniz_oxide"]),
+    ("range coder", &["baseline"]),
+    ("rle", &["baseline"]),
+    ("rust-lzma", &["rust-lzma"]),
+    ("ruzstd", &["ruzstd"]),
+    ("snap", &["snap", "chunked"]),
+    ("tetsy_snappy", &["tetsy-snappy"]),
+    ("uncompressed", &["uncompressed"]),
+    ("xsnappy", &["xsnappy"]),
+    ("xz (cli)", &["cli"]),
+    ("xz2", &["xz2"]),
+    ("yazi", &["yazi"]),
+    ("zopfli", &["zopfli"]),
+    ("zopfli-rs", &["zopfli-rs"]),
+    ("zstd", &["zstd"]),
+    ("zstd (cli)", &["cli"]),
+];
+
+/// The scheme, settings and crate that decompress each format for `decompress --detect`. Any
+/// level decompresses the same, so the default ones are used.
+const FORMAT_SCHEMES: &[(Format, &str, &str, &str)] = &[
+    (
+        Format::Gzip,
+        "flate2 (rust)",
+        "gzip / level 6",
+        "flate2-rust",
+    ),
+    (
+        Format::Zlib,
+        "flate2 (rust)",
+        "zlib / level 6",
+        "flate2-rust",
+    ),
+    (Format::Zstd, "zstd", "level 3", "zstd"),
+    (Format::Xz, "xz2", "preset 6", "xz2"),
+    (Format::LzmaAlone, "xz2", "preset 6 / lzma", "xz2"),
+    (Format::Bzip2, "bzip2", "level 9", "bzip2"),
+    (Format::Lz4Frame, "lz4_flex", "safe / Frame", "lz4_flex"),
+    (Format::SnappyFramed, "snap", "Frame", "snap"),
+];
+
+fn schemes_dir() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../schemes")
+}
+
+struct Request {
+    command: Command,
+    scheme: Option<String>,
+    settings: Option<String>,
+    input: Option<std::path::PathBuf>,
+    output: Option<std::path::PathBuf>,
+}
+
+impl Request {
+    /// Run the scheme crate `name` (a directory in `schemes`) with this request and return what
+    /// it printed, which is empty if none of its schemes matches. If it fails, the rows it printed
+    /// for list and detect are passed through.
+    fn run(&self, name: &str) -> anyhow::Result<String> {
+        let manifest = schemes_dir().join(name).join("Cargo.toml");
+        anyhow::ensure!(manifest.is_file(), "there is no scheme crate {name}");
+        let mut cargo =
+            std::process::Command::new(std::env::var_os("CARGO").unwrap_or("cargo".into()));
+        cargo
+            .args(["run", "--release", "--quiet", "--manifest-path"])
+            .arg(manifest)
+            .env(COMMAND_VAR, self.command.to_string())
+            .stderr(std::process::Stdio::inherit());
+        for (var, value) in [
+            (SCHEME_VAR, self.scheme.as_ref().map(std::ffi::OsStr::new)),
+            (
+                SETTINGS_VAR,
+                self.settings.as_ref().map(std::ffi::OsStr::new),
+            ),
+            (INPUT_VAR, self.input.as_ref().map(|path| path.as_os_str())),
+            (
+                OUTPUT_VAR,
+                self.output.as_ref().map(|path| path.as_os_str()),
+            ),
+        ] {
+            match value {
+                Some(value) => cargo.env(var, value),
+                None => cargo.env_remove(var),
+            };
+        }
+        let output = cargo.output().context("couldn't run cargo")?;
+        if !output.status.success() && matches!(self.command, Command::List | Command::Detect) {
+            // the rows before the failure
+            print!("{}", String::from_utf8_lossy(&output.stdout));
+        }
+        anyhow::ensure!(
+            output.status.success(),
+            "scheme crate {name} failed: {}",
+            output.status
+        );
+        String::from_utf8(output.stdout).context("scheme crate printed invalid UTF-8")
+    }
+
+    /// Run the first crate with a scheme that matches, `crates` are all candidates.
+    fn run_matching(&self, crates: &[&str]) -> anyhow::Result<()> {
+        for name in crates {
+            let printed = self.run(name)?;
+            if !printed.is_empty() {
+                print!("{printed}");
+                return Ok(());
+            }
+        }
+        let scheme = self.scheme.as_deref().unwrap_or_default();
+        match &self.settings {
+            Some(settings) => anyhow::bail!(
+                "there is no scheme {scheme} with settings '{settings}', see ccmp list"
+            ),
+            None => anyhow::bail!("there is no scheme {scheme} without settings, see ccmp list"),
+        }
+    }
+}
+
+fn all_crates() -> anyhow::Result<std::vec::Vec<String>> {
+    let mut crates = vec![];
+    for entry in std::fs::read_dir(schemes_dir()).context("couldn't list scheme crates")? {
+        let entry = entry?;
+        if entry.path().join("Cargo.toml").is_file() {
+            crates.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    crates.sort();
+    Ok(crates)
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let command: Command = args.next().context(USAGE)?.parse().context(USAGE)?;
+    let (mut scheme, mut settings, mut crate_name) = (None, None, None);
+    let mut detect = false;
+    let mut paths = vec![];
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--scheme" => scheme = Some(value()?),
+            "--settings" => settings = Some(value()?),
+            "--crate" => crate_name = Some(value()?),
+            "--detect" => detect = true,
+            _ if arg.starts_with("--") => anyhow::bail!("unexpected argument {arg}\n{USAGE}"),
+            _ => paths.push(std::path::PathBuf::from(arg)),
+        }
+    }
+    if command == Command::Detect && paths.len() == 1 {
+        let data = std::fs::read(&paths[0])
+            .with_context(|| format!("couldn't read {}", paths[0].display()))?;
+        match common::detect::detect(&data) {
+            Some(format) => println!("{format}"),
+            None => anyhow::bail!("unknown format"),
+        }
+        return Ok(());
+    }
+    let expected_paths = match command {
+        Command::List | Command::Detect => 0,
+        Command::Compress | Command::Decompress => 2,
+        Command::Roundtrip | Command::Benchmark | Command::Probe => 1,
+    };
+    anyhow::ensure!(
+        paths.len() == expected_paths,
+        "{command} expects {expected_paths} paths\n{USAGE}"
+    );
+    anyhow::ensure!(
+        matches!(
+            command,
+            Command::Compress | Command::Roundtrip | Command::Benchmark
+        ) == scheme.is_some(),
+        "{command} {} a scheme\n{USAGE}",
+        if scheme.is_some() {
+            "doesn't take"
+        } else {
+            "needs"
+        }
+    );
+    anyhow::ensure!(
+        settings.is_none() || scheme.is_some(),
+        "--settings needs --scheme\n{USAGE}"
+    );
+    anyhow::ensure!(
+        !detect || (command == Command::Decompress && crate_name.is_none()),
+        "--detect is only for decompress, without --crate\n{USAGE}"
+    );
+    // the scheme crates run in another directory
+    let mut paths = paths
+        .into_iter()
+        .map(std::path::absolute)
+        .collect::<std::io::Result<std::vec::Vec<_>>>()
+        .context("invalid path")?
+        .into_iter();
+    let mut request = Request {
+        command,
+        scheme,
+        settings,
+        input: paths.next(),
+        output: paths.next(),
+    };
+
+    // benchmarks run on a sample of the input
+    let sample = match command {
+        Command::Probe | Command::Benchmark => {
+            let input = request.input.as_ref().expect("checked above");
+            Some(probe::SampleFile::new(input)?)
+        }
+        _ => None,
+    };
+    if let Some(sample) = &sample {
+        request.input = Some(sample.path().to_path_buf());
+    }
+
+    if matches!(command, Command::List | Command::Detect | Command::Probe) {
+        let crates = match crate_name {
+            Some(name) => vec![name],
+            None => all_crates()?,
+        };
+        if command == Command::Probe {
+            return probe::probe(&request, &crates);
+        }
+        if command == Command::List {
+            println!("scheme,settings");
+        } else {
+            println!("scheme,settings,format,detected");
+        }
+        // a crate that fails (or can't be built here) shouldn't hide the others
+        let mut failed = vec![];
+        for name in crates {
+            match request.run(&name) {
+                Ok(printed) => print!("{printed}"),
+                Err(e) => {
+                    eprintln!("{name}: {e:?}");
+                    failed.push(name);
+                }
+            }
+        }
+        // for detect, the failure of any crate is a failed check
+        anyhow::ensure!(
+            command == Command::List || failed.is_empty(),
+            "detection check failed for {}",
+            failed.join(", ")
+        );
+        return Ok(());
+    }
+
+    if command == Command::Decompress && detect {
+        // data of unknown provenance, without a ccmp header
+        let input = request.input.as_ref().expect("checked above");
+        let data =
+            std::fs::read(input).with_context(|| format!("couldn't read {}", input.display()))?;
+        let format = common::detect::detect(&data).context("unknown format")?;
+        let &(_, scheme, settings, name) = FORMAT_SCHEMES
+            .iter()
+            .find(|(f, ..)| *f == format)
+            .expect("every format has a scheme");
+        eprintln!("detected {format}, decompressing with {scheme} ({settings})");
+        request.scheme = Some(scheme.to_string());
+        request.settings = Some(settings.to_string());
+        return request
+            .run_matching(&[name])
+            .with_context(|| format!("{command} failed"));
+    }
+    if command == Command::Decompress {
+        let input = request.input.as_ref().expect("checked above");
+        let header = common::ccmp::read_header(input)?;
+        request.scheme = Some(header.scheme);
+        request.settings = header.settings;
+    }
+    let scheme = request.scheme.as_deref().expect("set above");
+    let crates: &[&str] = match &crate_name {
+        Some(name) => &[name.as_str()],
+        None => SCHEME_CRATES
+            .iter()
+            .find(|(name, _)| *name == scheme)
+            .map(|(_, crates)| *crates)
+            .with_context(|| {
+                format!("unknown scheme {scheme}, pass the crate that has it with --crate")
+            })?,
+    };
+    request
+        .run_matching(crates)
+        .with_context(|| format!("{command} failed"))
+}
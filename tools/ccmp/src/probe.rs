@@ -0,0 +1,203 @@
+//! `ccmp probe`: recommend schemes for the data of the user. Every scheme compresses and
+//! decompresses a part of the sample once, in parallel (see `common::probe::SWEEP_SIZE`), then the
+//! schemes with the best trade-offs between ratio and speed are benchmarked on the whole sample
+//! like on the corpora.
+
+use crate::Request;
+use anyhow::Context as _;
+use common::ccmp::Command;
+
+/// The speeds of a single run next to other schemes on the other cores can be off by this much, so
+/// schemes within it of the best trade-off are benchmarked too.
+const SWEEP_SLACK: f64 = 0.5;
+
+/// A row printed by `probe` and `benchmark`, see `common::Result`.
+#[derive(serde::Deserialize)]
+struct Row {
+    scheme: String,
+    settings: Option<String>,
+    _corpus: String,
+    compression_speed: Option<f64>,
+    _compression_speed_std: Option<f64>,
+    decompression_speed: Option<f64>,
+    _decompression_speed_std: Option<f64>,
+    compression_ratio: f64,
+}
+
+#[derive(serde::Serialize)]
+struct Recommendation<'a> {
+    rank: usize,
+    scheme: &'a str,
+    settings: Option<&'a str>,
+    compression_ratio: f64,
+    compression_speed: Option<f64>,
+    decompression_speed: Option<f64>,
+    // the speeds at which no other scheme has a better ratio
+    optimal_for: String,
+}
+
+fn parse_rows(printed: &str) -> anyhow::Result<std::vec::Vec<Row>> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(printed.as_bytes())
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .context("scheme crate printed invalid rows")
+}
+
+/// The indices of the rows that no other row beats in both ratio and `speed`, and of the rows
+/// within `slack` of their speed.
+fn pareto_front(rows: &[Row], speed: fn(&Row) -> Option<f64>, slack: f64) -> std::vec::Vec<usize> {
+    let mut indices: std::vec::Vec<usize> = (0..rows.len())
+        .filter(|&i| speed(&rows[i]).is_some())
+        .collect();
+    indices.sort_by(|&a, &b| {
+        let (a, b) = (&rows[a], &rows[b]);
+        b.compression_ratio
+            .total_cmp(&a.compression_ratio)
+            .then(speed(b).unwrap().total_cmp(&speed(a).unwrap()))
+    });
+    let mut fastest = 0.0f64;
+    let mut front = vec![];
+    for i in indices {
+        let speed = speed(&rows[i]).unwrap();
+        if speed * (1.0 + slack) > fastest {
+            front.push(i);
+        }
+        fastest = fastest.max(speed);
+    }
+    front
+}
+
+fn compression_speed(row: &Row) -> Option<f64> {
+    row.compression_speed
+}
+
+fn decompression_speed(row: &Row) -> Option<f64> {
+    row.decompression_speed
+}
+
+/// A sample of the input of the user, which is removed when dropped.
+pub(crate) struct SampleFile(std::path::PathBuf);
+
+impl SampleFile {
+    /// Sample the file or tree at `input` (see `common::probe`) into a temporary file, so that
+    /// every scheme crate gets the same data.
+    pub(crate) fn new(input: &std::path::Path) -> anyhow::Result<Self> {
+        let sample = common::probe::sample(input)
+            .with_context(|| format!("couldn't sample {}", input.display()))?;
+        eprintln!(
+            "sampled {} of {} bytes in {} of {} files",
+            sample.data.len(),
+            sample.size,
+            sample.sampled_files,
+            sample.files
+        );
+        let path = std::env::temp_dir().join(format!("ccmp-probe-{}", std::process::id()));
+        std::fs::write(&path, &sample.data)
+            .with_context(|| format!("couldn't write {}", path.display()))?;
+        Ok(Self(path))
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl Drop for SampleFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Probe every scheme of `crates` with `request`, whose input is a [`SampleFile`], and print the
+/// recommended schemes as csv, from the best ratio to the fastest.
+pub(crate) fn probe(request: &Request, crates: &[String]) -> anyhow::Result<()> {
+    // a crate that fails (or can't be built here) shouldn't hide the others
+    let mut rows = vec![];
+    let mut crate_names = vec![];
+    for name in crates {
+        eprintln!("probing {name}");
+        match request.run(name).and_then(|printed| parse_rows(&printed)) {
+            Ok(printed) => {
+                crate_names.extend(std::iter::repeat_n(name, printed.len()));
+                rows.extend(printed);
+            }
+            Err(e) => eprintln!("{name}: {e:?}"),
+        }
+    }
+    anyhow::ensure!(!rows.is_empty(), "no scheme could be probed");
+
+    let mut candidates = pareto_front(&rows, compression_speed, SWEEP_SLACK);
+    candidates.extend(pareto_front(&rows, decompression_speed, SWEEP_SLACK));
+    candidates.sort();
+    candidates.dedup();
+    let mut timed = vec![];
+    for (n, &i) in candidates.iter().enumerate() {
+        let row = &rows[i];
+        let request = Request {
+            command: Command::Benchmark,
+            scheme: Some(row.scheme.clone()),
+            settings: row.settings.clone(),
+            input: request.input.clone(),
+            output: None,
+        };
+        eprintln!(
+            "benchmarking {}/{}: {}{}",
+            n + 1,
+            candidates.len(),
+            row.scheme,
+            row.settings
+                .as_ref()
+                .map(|settings| format!(" ({settings})"))
+                .unwrap_or_default()
+        );
+        match request
+            .run(crate_names[i])
+            .and_then(|printed| parse_rows(&printed))
+        {
+            Ok(printed) => timed.extend(printed),
+            Err(e) => eprintln!("{}: {e:?}", crate_names[i]),
+        }
+    }
+
+    let compression_front = pareto_front(&timed, compression_speed, 0.0);
+    let decompression_front = pareto_front(&timed, decompression_speed, 0.0);
+    let mut recommended: std::vec::Vec<usize> = compression_front
+        .iter()
+        .chain(decompression_front.iter())
+        .copied()
+        .collect();
+    recommended.sort_by(|&a, &b| {
+        timed[b]
+            .compression_ratio
+            .total_cmp(&timed[a].compression_ratio)
+            .then(a.cmp(&b))
+    });
+    recommended.dedup();
+    let mut writer = csv::WriterBuilder::new()
+        .quote_style(csv::QuoteStyle::Necessary)
+        .from_writer(std::io::stdout());
+    for (rank, i) in recommended.into_iter().enumerate() {
+        let row = &timed[i];
+        let optimal_for = [
+            (compression_front.contains(&i), "compression"),
+            (decompression_front.contains(&i), "decompression"),
+        ]
+        .into_iter()
+        .filter_map(|(optimal, speed)| optimal.then_some(speed))
+        .collect::<std::vec::Vec<_>>()
+        .join(" / ");
+        writer.serialize(Recommendation {
+            rank: rank + 1,
+            scheme: &row.scheme,
+            settings: row.settings.as_deref(),
+            compression_ratio: row.compression_ratio,
+            compression_speed: row.compression_speed,
+            decompression_speed: row.decompression_speed,
+            optimal_for,
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}